@@ -1,33 +1,474 @@
+; Reset is made of parts in sections of their own, which reset.x places one after the other. Each
+; part falls through to the next, so the parts of a disabled feature are left out without leaving
+; anything behind, and link.x discards them. This one only marks where Reset starts.
   .section .Reset, "ax"
   .global Reset
   .type Reset,%function
 Reset:
+
+; With the `boot-info` feature, keep SR and SP as Reset found them in R8 and R9, and the reset cause
+; in R10, which nothing clobbers until .Reset.boot_info writes them to `BootInfo`. __SYSRSTIV is
+; SYSRSTIV if memory.x sets __SYSRSTIV_ADDR, and a word of 0 in ROM otherwise. Reading SYSRSTIV
+; also clears the reset cause, so Reset reads it once, here.
+  .section .Reset.boot_info_entry, "ax"
+  mov r2, r8
+  mov r1, r9
+  mov &__SYSRSTIV, r10
+
+  .section .Reset.start, "ax"
   mov #_stack_start, r1
+
+; Hold the watchdog if memory.x told us where it is (__WDTCTL_ADDR != 0).
+  mov #__WDTCTL_ADDR, r4
+  tst r4
+  jz .done_wdt
+  mov #0x5a80, 0(r4) ; WDTPW | WDTHOLD
+
+.done_wdt:
+
+; With both the `scrub-ram` and `retain-ram` features, RAM that .Reset.retain_check is going to keep
+; isn't scrubbed: the same check, of the magic word and the checksum in __RAM_RETENTION, skips
+; .Reset.scrub_ram.
+  .section .Reset.scrub_check, "ax"
+  cmp #0x5afe, &__RAM_RETENTION
+  jne .scrub
+  call #__RetainChecksum
+  cmp r12, &__RAM_RETENTION+2
+  jne .scrub
+  br #.done_scrub ; Right after .Reset.scrub_ram
+.scrub:
+
+; RAM scrubbing, with the `scrub-ram` feature
+; Zero all of RAM, _sscrub.._escrub, so that nothing from a previous run or a bootloader is left in
+; it. This includes __BOOT_INFO, so every boot that scrubs RAM looks like a cold one.
+  .section .Reset.scrub_ram, "ax"
+  mov #_sscrub, r4
+.more_scrub:
+  clr 0(r4)
+  incd r4
+  cmp #_escrub, r4
+  jlo .more_scrub ; r4 < _escrub
+.done_scrub:
+
+; LPMx.5 wake-up, with the `lpm5-wakeup` feature
+; A wake-up from LPMx.5 (SYSRSTIV_LPM5WU in R10) goes through the reset vector too; then
+; Lpm5Wakeup, the `#[lpm5_wakeup]` handler, gets to run before anything else. It either goes back
+; to LPMx.5 or returns to carry on with a normal boot.
+  .section .Reset.lpm5_wakeup, "ax"
+  cmp #0x08, r10 ; SYSRSTIV_LPM5WU
+  jne .done_lpm5
+  call #Lpm5Wakeup
+
+.done_lpm5:
+
+; Boot info, with the `boot-info` feature
+; __BOOT_INFO survives resets, so finding the magic word in it means RAM kept its contents since the
+; last boot: count this reset. Otherwise this is a cold boot and the counter starts over.
+  .section .Reset.boot_info, "ax"
+  mov r8, &__BOOT_INFO+6
+  mov r9, &__BOOT_INFO+8
+  clr &__BOOT_INFO+10
+  mov r10, &__BOOT_INFO+12
+  cmp #0xb007, &__BOOT_INFO
+  jne .cold_boot
+  inc &__BOOT_INFO+2
+  clr &__BOOT_INFO+4
+  jmp .done_boot
+.cold_boot:
+  mov #0xb007, &__BOOT_INFO
+  clr &__BOOT_INFO+2
+  mov #1, &__BOOT_INFO+4
+.done_boot:
+
+; Stack painting, with the `paint-stack` feature
+; Fill the stack reservation, _spaint.._stack_start, with the pattern `stack_high_water_mark` scans
+; for. Nothing is on the stack yet.
+  .section .Reset.paint_stack, "ax"
+  mov #_spaint, r4
+.more_paint:
+  mov #0xcdcd, 0(r4)
+  incd r4
+  cmp #_stack_start, r4
+  jlo .more_paint ; r4 < _stack_start
+
+  .section .Reset.pre_init, "ax"
   call #PreInit
 
-; .bss init
-  clr r4
-.more_bss:
-  cmp bss_size, r4
-  jhs .done_bss ; r4 >= bss_size
-  clr.b _sbss(r4) ; Zero out RAM.
-  inc r4
-  jmp .more_bss
-
-; .data init
-.done_bss:
+; RAM retention, with the `retain-ram` feature
+; Once RAM is initialized, Reset stores a magic word and a checksum of the retained sections in
+; __RAM_RETENTION, and `seal_ram` stores the checksum again whenever the program asks for it. If
+; both are still in place and the checksum matches what's in RAM, this is a warm reset of a program
+; with the same layout and RAM is left as it is, but for .ramtext: it's code, which may have
+; changed without changing the layout, so it's copied again. Otherwise the magic word is cleared
+; before the RAM init, so that a reset in the middle of it doesn't leave a half-initialized RAM
+; looking valid.
+  .section .Reset.retain_check, "ax"
+  cmp #0x5afe, &__RAM_RETENTION
+  jne .invalidate_ram
+  call #__RetainChecksum
+  cmp r12, &__RAM_RETENTION+2
+  jne .invalidate_ram
+  mov #1, &__BOOT_INFO+10
+  mov #_siramtext, r5
+  mov #_sramtext, r4
+  jmp .check_ramtext
+.more_ramtext:
+  mov @r5+, r6
+  mov r6, 0(r4)
+  incd r4
+.check_ramtext:
+  cmp #_eramtext, r4
+  jlo .more_ramtext ; r4 < _eramtext
+  br #.ram_retained ; Right after .Reset.retain_store
+.invalidate_ram:
+  clr &__RAM_RETENTION
+
+; RAM init
+; link.x lists the sections to zero, as (destination, length) pairs, and the sections to copy from
+; ROM, as (source, destination, length) triples, in two tables. Both ends of every section are
+; 2-byte aligned, so they're initialized a word at a time. The bounds are checked before the first
+; store, so an empty section costs nothing but the compare.
+  .section .Reset.init_ram, "ax"
+  mov #__zero_table_start, r8
+  jmp .check_zero_table
+.next_zero:
+  mov @r8+, r4 ; Destination
+  mov @r8+, r7 ; Length
+  add r4, r7 ; End of the destination
+  jmp .check_zero
+.more_zero:
+  clr 0(r4) ; Zero out RAM.
+  incd r4
+.check_zero:
+  cmp r7, r4
+  jlo .more_zero ; r4 < end
+.check_zero_table:
+  cmp #__zero_table_end, r8
+  jlo .next_zero
+
+  mov #__copy_table_start, r8
+  jmp .check_copy_table
+.next_copy:
+  mov @r8+, r5 ; Source
+  mov @r8+, r4 ; Destination
+  mov @r8+, r7 ; Length
+  add r4, r7 ; End of the destination
+  jmp .check_copy
+.more_copy:
+  mov @r5+, r6 ; Copy from ROM to RAM.
+  mov r6, 0(r4)
+  incd r4
+.check_copy:
+  cmp r7, r4
+  jlo .more_copy ; r4 < end
+.check_copy_table:
+  cmp #__copy_table_end, r8
+  jlo .next_copy
+
+; .upper.bss and .upper.data init, with the `upper-memory` feature
+  .section .Reset.upper_init, "ax"
+  call #UpperInit
+
+  .section .Reset.retain_store, "ax"
+  call #__RetainChecksum
+  mov r12, &__RAM_RETENTION+2
+  mov #0x5afe, &__RAM_RETENTION
+.ram_retained:
+
+; MPU, with the `fram-mpu` feature
+; mpu.x sets __MPUCTL0_ADDR, and link.x provides the segment borders and access rights. Writing
+; anything but the password to the upper byte of MPUCTL0 closes the MPU registers again.
+  .section .Reset.mpu, "ax"
+  mov #0xa500, &__MPUCTL0_ADDR ; MPUPW
+  mov #__MPUSEGB2, &__MPUCTL0_ADDR+4
+  mov #__MPUSEGB1, &__MPUCTL0_ADDR+6
+  mov #__MPUSAM, &__MPUCTL0_ADDR+8
+  mov #0xa501, &__MPUCTL0_ADDR ; MPUPW | MPUENA
+  clr.b &__MPUCTL0_ADDR+1
+
+; Register scrubbing, with the `scrub-ram` feature
+; R4 to R15 are cleared so that nothing that Reset, PreInit or a bootloader left in them reaches
+; main.
+  .section .Reset.scrub_regs, "ax"
   clr r4
-.more_data:
-  cmp data_size, r4
-  jhs .done_data ; r4 >= data_size
-  mov.b _sidata(r4), _sdata(r4) ; Copy from ROM to RAM.
-  inc r4
-  jmp .more_data
-
-.done_data:
+  clr r5
+  clr r6
+  clr r7
+  clr r8
+  clr r9
+  clr r10
+  clr r11
+  clr r12
+  clr r13
+  clr r14
+  clr r15
+
+  .section .Reset.main, "ax"
   br #main
 
-.section .rodata, "a"
-.align 2 ; MSP430 will not behave properly w/ unaligned reads, from my testing.
-bss_size: .word _ebss - _sbss
-data_size: .word _edata - _sdata
+; What Reset reads instead of SYSRSTIV when memory.x doesn't set __SYSRSTIV_ADDR: no reset cause.
+; Only the `boot-info` feature refers to this
+  .section .rodata.__NoResetCause, "a"
+  .balign 2
+  .global __NoResetCause
+__NoResetCause:
+  .short 0
+
+; Zeroes .upper.bss and initializes .upper.data. With the `upper-memory` feature these sections can
+; be above 0x10000, out of reach of the 16-bit loops in Reset, so this uses the MSP430X address
+; instructions. The MSP430 variant of the blob spells them out as `.word`s, which lets it link with
+; code built for any device. Only .Reset.upper_init refers to UpperInit, so without the feature it's
+; never called and --gc-sections drops it.
+  .section .text.UpperInit, "ax"
+  .global UpperInit
+UpperInit:
+; Same as the RAM init in Reset, but the tables hold 20-bit addresses and lengths, in 32 bits each.
+; The tables themselves are below 0x10000.
+  mov #__upper_zero_table_start, r8
+  jmp .check_upper_zero_table
+.next_upper_zero:
+.ifdef CPUX
+  mova @r8+, r4 ; Destination
+  mova @r8+, r7 ; Length
+  adda r4, r7 ; End of the destination
+.else
+  .word 0x0814 ; mova @r8+, r4 (destination)
+  .word 0x0817 ; mova @r8+, r7 (length)
+  .word 0x04e7 ; adda r4, r7
+.endif
+  jmp .check_upper_zero
+.more_upper_zero:
+  clr 0(r4) ; Zero out RAM. Indexed mode uses all 20 bits of r4 when it's above 0xFFFF.
+.ifdef CPUX
+  incda r4
+.check_upper_zero:
+  cmpa r7, r4
+.else
+  .word 0x00a4, 2 ; adda #2, r4
+.check_upper_zero:
+  .word 0x07d4 ; cmpa r7, r4
+.endif
+  jlo .more_upper_zero ; r4 < end
+.check_upper_zero_table:
+  cmp #__upper_zero_table_end, r8
+  jlo .next_upper_zero
+
+  mov #__upper_copy_table_start, r8
+  jmp .check_upper_copy_table
+.next_upper_copy:
+.ifdef CPUX
+  mova @r8+, r5 ; Source
+  mova @r8+, r4 ; Destination
+  mova @r8+, r7 ; Length
+  adda r4, r7 ; End of the destination
+.else
+  .word 0x0815 ; mova @r8+, r5 (source)
+  .word 0x0814 ; mova @r8+, r4 (destination)
+  .word 0x0817 ; mova @r8+, r7 (length)
+  .word 0x04e7 ; adda r4, r7
+.endif
+  jmp .check_upper_copy
+.more_upper_copy:
+  mov @r5, r6 ; Copy from HIROM to HIRAM.
+  mov r6, 0(r4)
+.ifdef CPUX
+  incda r5
+  incda r4
+.check_upper_copy:
+  cmpa r7, r4
+.else
+  .word 0x00a5, 2 ; adda #2, r5
+  .word 0x00a4, 2 ; adda #2, r4
+.check_upper_copy:
+  .word 0x07d4 ; cmpa r7, r4
+.endif
+  jlo .more_upper_copy ; r4 < end
+.check_upper_copy_table:
+  cmp #__upper_copy_table_end, r8
+  jlo .next_upper_copy
+  ret
+
+; Returns in r12 a checksum of the sections that `retain-ram` keeps, seeded with the tag of the RAM
+; layout: each word is xored in after rotating the sum left by one bit. link.x lists the sections
+; as (start, length) pairs, in a table of 16-bit addresses and one of 20-bit addresses for the
+; upper memory sections, which is empty without the `upper-memory` feature. This only uses r12 to
+; r15, so that `seal_ram` can call it as an `extern "C" fn() -> u16`, and it's only referred to by
+; .Reset.retain_check, .Reset.retain_store and `seal_ram`, so without the feature --gc-sections
+; drops it.
+  .section .text.__RetainChecksum, "ax"
+  .global __RetainChecksum
+__RetainChecksum:
+  mov #__RETAIN_TAG, r12
+  mov #__retain_table_start, r13
+  jmp .check_retain_table
+.next_retain:
+  mov @r13+, r14 ; Start
+  mov @r13+, r15 ; Length
+  add r14, r15 ; End
+  jmp .check_retain
+.more_retain:
+  rla r12 ; Rotate left: rla moves bit 15 to C, which adc puts in bit 0
+  adc r12
+  xor @r14+, r12
+.check_retain:
+  cmp r15, r14
+  jlo .more_retain ; r14 < end
+.check_retain_table:
+  cmp #__retain_table_end, r13
+  jlo .next_retain
+
+  mov #__upper_retain_table_start, r13
+  jmp .check_upper_retain_table
+.next_upper_retain:
+.ifdef CPUX
+  mova @r13+, r14 ; Start
+  mova @r13+, r15 ; Length
+  adda r14, r15 ; End
+.else
+  .word 0x0d1e ; mova @r13+, r14 (start)
+  .word 0x0d1f ; mova @r13+, r15 (length)
+  .word 0x0eef ; adda r14, r15
+.endif
+  jmp .check_upper_retain
+.more_upper_retain:
+  rla r12
+  adc r12
+  xor @r14, r12 ; Indirect mode uses all 20 bits of r14 when it's above 0xFFFF.
+.ifdef CPUX
+  incda r14
+.check_upper_retain:
+  cmpa r15, r14
+.else
+  .word 0x00ae, 2 ; adda #2, r14
+.check_upper_retain:
+  .word 0x0fde ; cmpa r15, r14
+.endif
+  jlo .more_upper_retain ; r14 < end
+.check_upper_retain_table:
+  cmp #__upper_retain_table_end, r13
+  jlo .next_upper_retain
+  ret
+
+; Per-vector trampolines for the default interrupt handler
+; __DefaultHandler<n> calls __DEFAULT_DISPATCH, which tells n from the return address. They share
+; one section, so the code after them doesn't move when a build uses other vectors.
+  .section .text.__DefaultHandlers, "ax"
+  .global __DefaultHandlers
+__DefaultHandlers:
+  .macro default_handler n
+  .global __DefaultHandler\n
+__DefaultHandler\n:
+  call #__DEFAULT_DISPATCH
+  .endm
+  .irp n, 0,1,2,3,4,5,6,7,8,9,10,11,12,13,14,15,16,17,18,19,20,21,22,23,24,25,26,27,28,29
+  default_handler \n
+  .endr
+  .irp n, 30,31,32,33,34,35,36,37,38,39,40,41,42,43,44,45,46,47,48,49,50,51,52,53,54,55,56,57,58,59
+  default_handler \n
+  .endr
+  .irp n, 60,61,62
+  default_handler \n
+  .endr
+
+; Calls `DefaultHandlerIrq(irq: u8)` with the index of the trampoline that called it, if the
+; program defines it (`#[interrupt] fn DefaultHandler(irq: u8)`). Otherwise the stack is restored
+; to what the interrupt left and DefaultHandler takes over, as if it had been in the vector table.
+; This one saves the lower 16 bits of the registers, which is all that MSP430 CPUs have.
+  .section .text.__DefaultDispatch, "ax"
+  .weak DefaultHandlerIrq
+  .global __DefaultDispatch
+__DefaultDispatch:
+; Save the registers that the call clobbers.
+.ifdef CPUX
+  pushm.w #5, r15 ; r15 to r11
+.else
+  push r15
+  push r14
+  push r13
+  push r12
+  push r11
+.endif
+  mov 10(r1), r12 ; Return address, right after the 4-byte trampoline.
+  sub #__DefaultHandlers+4, r12
+  rra r12
+  rra r12
+  mov #DefaultHandlerIrq, r13
+  tst r13
+  jz .no_irq_handler
+  call r13
+.ifdef CPUX
+  popm.w #5, r15
+.else
+  pop r11
+  pop r12
+  pop r13
+  pop r14
+  pop r15
+.endif
+  incd r1 ; Drop the return address.
+  reti
+.no_irq_handler:
+.ifdef CPUX
+  popm.w #5, r15
+.else
+  pop r11
+  pop r12
+  pop r13
+  pop r14
+  pop r15
+.endif
+  incd r1
+  br #DefaultHandler
+
+; Same as __DefaultDispatch, but saves all 20 bits of r4 to r15, for MSP430X code that keeps
+; addresses above 0x10000 in them. DefaultHandlerIrq is Rust code built for the 16-bit MSP430, which
+; saves r4 to r10 with 16-bit pushes, so those need saving here as well. upper.x makes the
+; trampolines call this one.
+  .section .text.__DefaultDispatchX, "ax"
+  .weak DefaultHandlerIrq
+  .global __DefaultDispatchX
+__DefaultDispatchX:
+.ifdef CPUX
+  pushm.a #12, r15
+.else
+  .word 0x14bf ; pushm.a #12, r15
+.endif
+  mov 48(r1), r12
+  sub #__DefaultHandlers+4, r12
+  rra r12
+  rra r12
+  mov #DefaultHandlerIrq, r13
+  tst r13
+  jz .no_irq_handler_x
+  call r13
+.ifdef CPUX
+  popm.a #12, r15
+.else
+  .word 0x16b4 ; popm.a #12, r15
+.endif
+  incd r1
+  reti
+.no_irq_handler_x:
+.ifdef CPUX
+  popm.a #12, r15
+.else
+  .word 0x16b4 ; popm.a #12, r15
+.endif
+  incd r1
+  br #DefaultHandler
+
+; Layout: magic word, reset counter, cold boot flag, SR and SP at entry to Reset, RAM retained flag,
+; SYSRSTIV. Only the `boot-info` feature refers to this
+  .section .uninit.__BOOT_INFO, "aw", @nobits
+  .balign 2
+  .global __BOOT_INFO
+__BOOT_INFO:
+  .skip 14
+
+; Layout: magic word, checksum of the retained sections. Only the `retain-ram` feature refers to
+; this
+  .section .uninit.__RAM_RETENTION, "aw", @nobits
+  .balign 2
+  .global __RAM_RETENTION
+__RAM_RETENTION:
+  .skip 4
@@ -12,4 +12,8 @@ rm -f bin/*.a
 msp430-elf-as -mcpu=msp430 asm.s -o bin/$crate.o
 ar crs bin/msp430-none-elf.a bin/$crate.o
 
+# The same startup code, using the instructions of the MSP430X CPU
+msp430-elf-as -mcpu=msp430x --defsym CPUX=1 asm.s -o bin/$crate.o
+ar crs bin/msp430x-none-elf.a bin/$crate.o
+
 rm bin/$crate.o
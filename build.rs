@@ -1,22 +1,48 @@
-use std::{env, fs, fs::File, io::Write, path::PathBuf};
+use std::{
+    env, fs,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
 
 fn main() {
     let target = env::var("TARGET").unwrap();
     let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
 
     if target == "msp430-none-elf" {
-        fs::copy(format!("bin/{}.a", target), out_dir.join("libmsp430-rt.a")).unwrap();
+        // The MSP430X variant of the startup code only runs on CPUX devices, so it's only picked
+        // when the program is built for them
+        let blob = if is_cpux() {
+            "msp430x-none-elf"
+        } else {
+            "msp430-none-elf"
+        };
+        fs::copy(format!("bin/{}.a", blob), out_dir.join("libmsp430-rt.a")).unwrap();
         println!("cargo:rustc-link-lib=static=msp430-rt");
     }
 
     // Put the linker script somewhere the linker can find it
     let out = &PathBuf::from(env::var_os("OUT_DIR").unwrap());
     let link_x = include_bytes!("link.x.in");
-    if env::var_os("CARGO_FEATURE_DEVICE").is_some() {
-        let mut f = File::create(out.join("link.x")).unwrap();
+    let mut f = File::create(out.join("link.x")).unwrap();
+    f.write_all(link_x).unwrap();
 
-        f.write_all(link_x).unwrap();
+    write_reset(out);
+    write_init_tables(out);
+    write_optional_script(out, "mpu.x", "fram-mpu", include_bytes!("mpu.x.in"));
+    write_optional_script(out, "ram2.x", "ram2", include_bytes!("ram2.x.in"));
+    write_optional_script(out, "upper.x", "upper-memory", include_bytes!("upper.x.in"));
+    write_optional_script(out, "info.x", "info-mem", include_bytes!("info.x.in"));
+    write_optional_script(out, "ipe.x", "ipe", include_bytes!("ipe.x.in"));
+    write_optional_script(
+        out,
+        "signature.x",
+        "jtag-signature",
+        include_bytes!("signature.x.in"),
+    );
 
+    let device = env::var_os("CARGO_FEATURE_DEVICE").is_some();
+    if device {
         // *IMPORTANT*: The weak aliases (i.e. `PROVIDED`) must come *after* `EXTERN(__INTERRUPTS)`.
         // Otherwise the linker will ignore user defined interrupts and always populate the table
         // with the weak aliases.
@@ -28,13 +54,425 @@ fn main() {
 INCLUDE device.x"#
         )
         .unwrap();
+        write_vector_count_assert(&mut f, None);
     } else {
-        let mut f = File::create(out.join("link.x")).unwrap();
-        f.write_all(link_x).unwrap();
-    };
+        // The generic `__INTERRUPTS` fills the VECTORS region, all but the reset vector
+        let length = vectors_length();
+        write_interrupts(out, &mut f, (length / 2).saturating_sub(1));
+        write_vector_count_assert(&mut f, Some(length));
+    }
+
+    if env::var_os("CARGO_FEATURE_BOOT_INFO").is_some() {
+        writeln!(
+            f,
+            r#"
+/* Where Reset reads the reset cause from: SYSRSTIV, or a word of 0 (no cause) without it */
+__SYSRSTIV = __SYSRSTIV_ADDR ? __SYSRSTIV_ADDR : __NoResetCause;"#
+        )
+        .unwrap();
+    }
+
+    if env::var_os("CARGO_FEATURE_LPM5_WAKEUP").is_some() {
+        writeln!(
+            f,
+            r#"
+/* The function that the `#[lpm5_wakeup]` attribute defines. 0 means there is none */
+PROVIDE(Lpm5Wakeup = 0);
+
+/* Do not exceed this mark in the error messages below                                    | */
+ASSERT(Lpm5Wakeup, "
+ERROR(msp430-rt): The 'lpm5-wakeup' feature is enabled, but the program has no #[lpm5_wakeup]
+handler for Reset to call on a wake-up from LPMx.5");
+
+ASSERT(__SYSRSTIV_ADDR, "
+ERROR(msp430-rt): The 'lpm5-wakeup' feature is enabled, but __SYSRSTIV_ADDR is not set, so
+Reset can't tell a wake-up from LPMx.5. Set it to the address of SYSRSTIV in memory.x,
+e.g. `__SYSRSTIV_ADDR = 0x019E;` on x5xx, x6xx and FRxx devices");"#
+        )
+        .unwrap();
+    }
+
+    if env::var_os("CARGO_FEATURE_HOLD_WATCHDOG").is_some() {
+        writeln!(
+            f,
+            r#"
+/* Do not exceed this mark in the error messages below                                    | */
+ASSERT(__WDTCTL_ADDR != 0, "
+ERROR(msp430-rt): The 'hold-watchdog' feature is enabled, but __WDTCTL_ADDR is not set.
+Set it to the address of WDTCTL in memory.x, e.g. `__WDTCTL_ADDR = 0x0120;` on x1xx, x2xx
+(including G2) and x4xx devices or `__WDTCTL_ADDR = 0x015C;` on x5xx, x6xx and FRxx devices");"#
+        )
+        .unwrap();
+    }
+
+    // `PROFILE` is "debug" for every profile that inherits from `dev`
+    if env::var_os("CARGO_FEATURE_JTAG_SIGNATURE").is_some()
+        && env::var("PROFILE").unwrap() == "debug"
+    {
+        writeln!(
+            f,
+            r#"
+/* Only 0xFFFF and 0x0000 in both words leave JTAG access open */
+/* Do not exceed this mark in the error messages below                                    | */
+ASSERT((__JTAG_SIGNATURE_1 == 0xFFFF || __JTAG_SIGNATURE_1 == 0x0000) &&
+       (__JTAG_SIGNATURE_2 == 0xFFFF || __JTAG_SIGNATURE_2 == 0x0000), "
+ERROR(msp430-rt): The JTAG signature can lock JTAG access, which is not allowed in debug
+builds. Set __JTAG_SIGNATURE_1 and __JTAG_SIGNATURE_2 to 0xFFFF, or build with a profile
+based on `release` to program a locked image");"#
+        )
+        .unwrap();
+    }
+
+    if env::var_os("CARGO_FEATURE_SCRUB_RAM").is_some() {
+        writeln!(
+            f,
+            r#"
+/* The area Reset zeroes, which is all of RAM */
+_sscrub = ORIGIN(RAM);
+_escrub = ORIGIN(RAM) + LENGTH(RAM);"#
+        )
+        .unwrap();
+    }
+
+    if env::var_os("CARGO_FEATURE_RETAIN_RAM").is_some() {
+        let mut bounds = vec!["_sbss + _ebss + _sdata + _edata + _sramtext + _eramtext"];
+        if is_enabled("ram2") {
+            bounds.push("_sbss2 + _ebss2 + _sdata2 + _edata2");
+        }
+        if is_enabled("upper-memory") {
+            bounds.push("_supper_bss + _eupper_bss + _supper_data + _eupper_data");
+        }
+        writeln!(
+            f,
+            r#"
+/* A tag of the RAM layout, which seeds the checksum that Reset records in __RAM_RETENTION along
+   with the magic word, so that a program whose statics are elsewhere doesn't take over what's in
+   RAM */
+__RETAIN_TAG = ~({}) & 0xFFFF;"#,
+            bounds.join("\n  + ")
+        )
+        .unwrap();
+    }
+
+    if env::var_os("CARGO_FEATURE_PAINT_STACK").is_some() {
+        writeln!(
+            f,
+            r#"
+/* The area Reset paints for `stack_high_water_mark`: the stack reservation */
+_spaint = _stack_start - _stack_size;
+
+/* Do not exceed this mark in the error messages below                                    | */
+ASSERT(_stack_size != 0, "
+ERROR(msp430-rt): The 'paint-stack' feature is enabled, but _stack_size is not set, so there's
+no stack area to paint. Set it in memory.x, e.g. `_stack_size = 0x100;`");"#
+        )
+        .unwrap();
+    }
 
     println!("cargo:rustc-link-search={}", out_dir.display());
 
     println!("cargo:rerun-if-changed=build.rs");
     println!("cargo:rerun-if-changed=link.x.in");
+    println!("cargo:rerun-if-changed=mpu.x.in");
+    println!("cargo:rerun-if-changed=ram2.x.in");
+    println!("cargo:rerun-if-changed=upper.x.in");
+    println!("cargo:rerun-if-changed=info.x.in");
+    println!("cargo:rerun-if-changed=ipe.x.in");
+    println!("cargo:rerun-if-changed=signature.x.in");
+}
+
+// Whether `feature` of msp430-rt is enabled
+fn is_enabled(feature: &str) -> bool {
+    let var = format!("CARGO_FEATURE_{}", feature.to_uppercase().replace('-', "_"));
+    env::var_os(var).is_some()
+}
+
+// The sections of `Reset` in asm.s, in the order they run, and the features that each one needs
+const RESET_PARTS: &[(&str, &[&str])] = &[
+    (".Reset", &[]),
+    (".Reset.boot_info_entry", &["boot-info"]),
+    (".Reset.start", &[]),
+    (".Reset.scrub_check", &["scrub-ram", "retain-ram"]),
+    (".Reset.scrub_ram", &["scrub-ram"]),
+    (".Reset.lpm5_wakeup", &["lpm5-wakeup"]),
+    (".Reset.boot_info", &["boot-info"]),
+    (".Reset.paint_stack", &["paint-stack"]),
+    (".Reset.pre_init", &[]),
+    (".Reset.retain_check", &["retain-ram"]),
+    (".Reset.init_ram", &[]),
+    (".Reset.upper_init", &["upper-memory"]),
+    (".Reset.retain_store", &["retain-ram"]),
+    (".Reset.mpu", &["fram-mpu"]),
+    (".Reset.scrub_regs", &["scrub-ram"]),
+    (".Reset.main", &[]),
+];
+
+// Writes reset.x, which link.x includes in .text to place the parts of `Reset` that the enabled
+// features need. Each part falls through to the next one that's there
+fn write_reset(out: &Path) {
+    let mut f = File::create(out.join("reset.x")).unwrap();
+    for &(section, features) in RESET_PARTS {
+        if features.iter().all(|feature| is_enabled(feature)) {
+            writeln!(f, "KEEP(*({}));", section).unwrap();
+        }
+    }
+}
+
+// An entry of an init table, and the features it needs
+type TableEntry = (&'static str, &'static [&'static str]);
+
+// The tables of sections that `Reset` and `UpperInit` initialize and that `__RetainChecksum`
+// covers, and their entries
+const INIT_TABLES: &[(&str, &[TableEntry])] = &[
+    (
+        "zero_table",
+        &[
+            ("SHORT(_sbss); SHORT(_ebss - _sbss);", &[]),
+            ("SHORT(_sbss2); SHORT(_ebss2 - _sbss2);", &["ram2"]),
+        ],
+    ),
+    (
+        "copy_table",
+        &[
+            (
+                "SHORT(_sidata); SHORT(_sdata); SHORT(_edata - _sdata);",
+                &[],
+            ),
+            (
+                "SHORT(_siramtext); SHORT(_sramtext); SHORT(_eramtext - _sramtext);",
+                &[],
+            ),
+            (
+                "SHORT(_sidata2); SHORT(_sdata2); SHORT(_edata2 - _sdata2);",
+                &["ram2"],
+            ),
+        ],
+    ),
+    (
+        "upper_zero_table",
+        &[(
+            "LONG(_supper_bss); LONG(_eupper_bss - _supper_bss);",
+            &["upper-memory"],
+        )],
+    ),
+    (
+        "upper_copy_table",
+        &[(
+            "LONG(_siupper_data); LONG(_supper_data); LONG(_eupper_data - _supper_data);",
+            &["upper-memory"],
+        )],
+    ),
+    (
+        "retain_table",
+        &[
+            ("SHORT(_sbss); SHORT(_ebss - _sbss);", &["retain-ram"]),
+            ("SHORT(_sdata); SHORT(_edata - _sdata);", &["retain-ram"]),
+            (
+                "SHORT(_sbss2); SHORT(_ebss2 - _sbss2);",
+                &["retain-ram", "ram2"],
+            ),
+            (
+                "SHORT(_sdata2); SHORT(_edata2 - _sdata2);",
+                &["retain-ram", "ram2"],
+            ),
+        ],
+    ),
+    (
+        "upper_retain_table",
+        &[
+            (
+                "LONG(_supper_bss); LONG(_eupper_bss - _supper_bss);",
+                &["retain-ram", "upper-memory"],
+            ),
+            (
+                "LONG(_supper_data); LONG(_eupper_data - _supper_data);",
+                &["retain-ram", "upper-memory"],
+            ),
+        ],
+    ),
+];
+
+// Writes init_tables.x, which link.x includes in .init_tables, with the entries that the enabled
+// features need. Each table is bounded by `__<name>_start` and `__<name>_end`
+fn write_init_tables(out: &Path) {
+    let mut f = File::create(out.join("init_tables.x")).unwrap();
+    for &(table, entries) in INIT_TABLES {
+        writeln!(f, "__{}_start = .;", table).unwrap();
+        for &(entry, features) in entries {
+            if features.iter().all(|feature| is_enabled(feature)) {
+                writeln!(f, "{}", entry).unwrap();
+            }
+        }
+        writeln!(f, "__{}_end = .;", table).unwrap();
+    }
+}
+
+// Writes a script that link.x includes whether or not `feature` is enabled. It's empty when the
+// feature is disabled
+fn write_optional_script(out: &Path, name: &str, feature: &str, contents: &[u8]) {
+    if is_enabled(feature) {
+        fs::write(out.join(name), contents).unwrap();
+    } else {
+        let disabled = format!("/* The `{}` feature is disabled */\n", feature);
+        fs::write(out.join(name), disabled).unwrap();
+    }
+}
+
+// Returns `true` if the program is built for the MSP430X CPU, i.e. with `-C target-cpu=msp430x` or
+// `-C target-cpu=msp430xv2`. Without a `target-cpu` LLVM targets the MSP430 CPU
+fn is_cpux() -> bool {
+    let flags = env::var("CARGO_ENCODED_RUSTFLAGS").unwrap_or_default();
+    let mut flags = flags.split('\x1f');
+    let mut cpu = None;
+    while let Some(flag) = flags.next() {
+        let codegen = match flag {
+            "-C" | "--codegen" => flags.next(),
+            _ => flag
+                .strip_prefix("-C")
+                .or_else(|| flag.strip_prefix("--codegen=")),
+        };
+        // Like rustc, the last one wins
+        if let Some(value) = codegen.and_then(|c| c.strip_prefix("target-cpu=")) {
+            cpu = Some(value.to_string());
+        }
+    }
+
+    matches!(cpu.as_deref(), Some("msp430x") | Some("msp430xv2"))
+}
+
+// Number of trampolines (`__DefaultHandler<n>`) in asm.s
+const TRAMPOLINES: u32 = 63;
+
+// Length of the VECTORS region in bytes, from `MSP430_RT_VECTORS_LENGTH`. build.rs can't tell which
+// memory.x the program links with, so it defaults to 32 bytes, the vector table of the MSP430G2553,
+// and link.x reports a mismatch
+fn vectors_length() -> u32 {
+    println!("cargo:rerun-if-env-changed=MSP430_RT_VECTORS_LENGTH");
+    match env::var("MSP430_RT_VECTORS_LENGTH") {
+        Ok(length) => match parse_length(&length) {
+            Some(length) => length,
+            None => panic!(
+                "MSP430_RT_VECTORS_LENGTH must be a number of bytes, e.g. 0x80, not `{}`",
+                length
+            ),
+        },
+        Err(_) => 32,
+    }
+}
+
+// Parses a number the way ld does, e.g. `0x80`, `128` or `1K`
+fn parse_length(s: &str) -> Option<u32> {
+    let s = s.trim();
+    let (digits, scale) = match s.strip_suffix(&['K', 'k'][..]) {
+        Some(digits) => (digits, 1024),
+        None => match s.strip_suffix(&['M', 'm'][..]) {
+            Some(digits) => (digits, 1024 * 1024),
+            None => (s, 1),
+        },
+    };
+
+    let n = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+        None => digits.parse().ok()?,
+    };
+    n.checked_mul(scale)
+}
+
+// Writes `INTERRUPTS` and `VECTORS` for the generic `__INTERRUPTS` (see src/lib.rs). Vector n is
+// `__Vector<n>`, which link.x points to the trampoline `__DefaultHandler<n>` or to DefaultHandler
+fn write_interrupts(out: &Path, link: &mut File, interrupts: u32) {
+    if interrupts > TRAMPOLINES {
+        panic!(
+            "the VECTORS region has room for {} interrupt vectors, but msp430-rt only supports up \
+             to {} without the `device` feature",
+            interrupts, TRAMPOLINES
+        );
+    }
+
+    let mut f = File::create(out.join("interrupts.rs")).unwrap();
+    writeln!(f, "const INTERRUPTS: usize = {};", interrupts).unwrap();
+    writeln!(f, "extern \"msp430-interrupt\" {{").unwrap();
+    for n in 0..interrupts {
+        writeln!(f, "    fn __Vector{}();", n).unwrap();
+    }
+    writeln!(f, "}}").unwrap();
+    writeln!(
+        f,
+        "const VECTORS: [unsafe extern \"msp430-interrupt\" fn(); INTERRUPTS] = ["
+    )
+    .unwrap();
+    for n in 0..interrupts {
+        writeln!(f, "    __Vector{},", n).unwrap();
+    }
+    writeln!(f, "];").unwrap();
+
+    // The trampolines are kept by the branch of these expressions that is taken, so they're only
+    // linked when they have a handler to call. DefaultHandler is __VECTOR_FILL if that's set
+    writeln!(
+        link,
+        "\n/* The generic __INTERRUPTS holds the per-vector trampolines if there's a \
+         DefaultHandlerIrq,\n   DefaultHandler otherwise */"
+    )
+    .unwrap();
+    for n in 0..interrupts {
+        writeln!(
+            link,
+            "__Vector{0} = DEFINED(DefaultHandlerIrq) ? __DefaultHandler{0} : \
+             DefaultHandler;\n\
+             ASSERT(__Vector{0} < 0x10000, \"\n\
+             ERROR(msp430-rt): The handler of interrupt vector {0} is placed above 0x10000, \
+             but the\n\
+             vector table only holds 16-bit addresses\");",
+            n
+        )
+        .unwrap();
+    }
+}
+
+// Writes the check that `__INTERRUPTS` fills the VECTORS region. ld can't print numbers, but
+// link.x makes it report the difference as the overflow of the region. Without the `device`
+// feature, msp430-rt generated `__INTERRUPTS` for a VECTORS region of `generic_length` bytes, so
+// the message can say how many vectors it has
+fn write_vector_count_assert(f: &mut File, generic_length: Option<u32>) {
+    writeln!(
+        f,
+        "\n/* Do not exceed this mark in the error messages below{:>37} */",
+        "|"
+    )
+    .unwrap();
+    match generic_length {
+        Some(length) => writeln!(
+            f,
+            r#"ASSERT(_interrupts_actual == _interrupts_expected, "
+ERROR(msp430-rt): msp430-rt generated {} interrupt vectors (__INTERRUPTS) for a VECTORS
+region of {:#x} bytes, but LENGTH(VECTORS) in memory.x differs by the number of bytes that
+ld's region `VECTORS' overflowed error reports, 2 bytes per vector.
+Possible solutions, from most likely to less likely:
+- Set MSP430_RT_VECTORS_LENGTH to LENGTH(VECTORS) (see the msp430-rt documentation)
+- Fix LENGTH(VECTORS) in memory.x
+- Override __VECTORS_END_ADDR if your vector table is not placed at the regular vector location
+");"#,
+            (length / 2).saturating_sub(1),
+            length
+        ),
+        None => writeln!(
+            f,
+            r#"ASSERT(_interrupts_actual == _interrupts_expected, "
+ERROR(msp430-rt): The number of interrupt vectors in __INTERRUPTS doesn't match the length
+of the VECTORS region. ld's region `VECTORS' overflowed error reports the difference, 2
+bytes per vector.
+Possible solutions, from most likely to less likely:
+- Link to a svd2rust generated pac crate, if you are not
+- Fix LENGTH(VECTORS) in memory.x; it doesn't match the number of interrupts provided by
+  the pac crate
+- Disable the 'device' feature of msp430-rt to build a generic application; a dependency
+  may be enabling it
+- Override __VECTORS_END_ADDR if your vector table is not placed at the regular vector location
+");"#
+        ),
+    }
+    .unwrap();
 }
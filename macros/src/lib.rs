@@ -22,16 +22,18 @@ use syn::{
     parse_macro_input,
     punctuated::Punctuated,
     spanned::Spanned,
-    FnArg, Ident, Item, ItemFn, ItemStatic, Pat, PatIdent, PathArguments, PathSegment, ReturnType,
-    Stmt, Token, Type, TypePath, Visibility,
+    Attribute, AttributeArgs, FnArg, Ident, Item, ItemFn, ItemStatic, Lit, Meta, MetaNameValue,
+    NestedMeta, Pat, PatIdent, PathArguments, PathSegment, ReturnType, Stmt, Token, Type, TypePath,
+    Visibility,
 };
 
 /// Attribute to declare the entry point of the program
 ///
 /// The specified function will be called by the reset handler *after* RAM has been initialized.
 ///
-/// The type of the specified function must be `[unsafe] fn([<name>: CriticalSection]) -> !` (never
-/// ending function), where the `CriticalSection` argument is optional.
+/// The type of the specified function must be
+/// `[unsafe] fn([<name>: CriticalSection], [<name>: BootInfo]) -> !` (never ending function), where
+/// both arguments are optional.
 ///
 /// # Properties
 ///
@@ -78,6 +80,24 @@ use syn::{
 /// }
 /// ```
 ///
+/// - Boot information. The `BootInfo` argument is collected by the reset handler (see
+///   `msp430_rt::BootInfo`, which needs the `boot-info` feature of `msp430-rt`) and must come last.
+///
+/// ``` ignore
+/// use msp430_rt::{entry, BootInfo};
+///
+/// #[entry]
+/// fn main(boot: BootInfo) -> ! {
+///     if boot.is_warm_reset() {
+///         /* recover from a watchdog reset, crash counting, .. */
+///     }
+///
+///     loop {
+///         /* .. */
+///     }
+/// }
+/// ```
+///
 /// # Pre-entry Interrupt Enable
 ///
 /// If the argument `interrupt_enable` is passed into the macro, interrupts will be enabled
@@ -90,6 +110,8 @@ use syn::{
 /// interrupts are enabled and possibly pass its return value into the entry function, allowing
 /// pre-interrupt initialization to be done.
 ///
+/// In both forms, the entry function can take a `BootInfo` as its last parameter.
+///
 /// Note that a function marked with the entry attribute is allowed to take no input parameters
 /// even if `init` returns a value, due to implementation details. To reduce code size, it is
 /// strongly recommended to put `#[inline(always)]` on `init` if it's used nowhere else.
@@ -175,9 +197,11 @@ pub fn entry(args: TokenStream, input: TokenStream) -> TokenStream {
             ReturnType::Type(_, ref ty) => matches!(**ty, Type::Never(_)),
         };
 
+    // A trailing `BootInfo` argument is accepted in every form of `#[entry]`
+    let (inputs, boot_info) = extract_boot_info_arg(&f.sig.inputs);
     let pair = match &interrupt_enable {
-        Some(interrupt_enable) => interrupt_enable.extract_init_arg(&f.sig.inputs),
-        None => extract_critical_section_arg(&f.sig.inputs),
+        Some(interrupt_enable) => interrupt_enable.extract_init_arg(&inputs),
+        None => extract_critical_section_arg(&inputs),
     };
 
     if let (true, Ok(ParamArgPair { fn_param, fn_arg })) = (valid_signature, pair) {
@@ -218,18 +242,31 @@ pub fn entry(args: TokenStream, input: TokenStream) -> TokenStream {
         let arg_def = fn_arg
             .as_ref()
             .map(|arg| quote_spanned!(Span::mixed_site()=> let arg = #arg; ));
+        let boot_info_ident = boot_info
+            .as_ref()
+            .map(|_| Ident::new("boot_info", Span::mixed_site()));
+        let (boot_info_param, boot_info_def) = match boot_info {
+            Some(ParamArgPair { fn_param, fn_arg }) => (
+                fn_param,
+                Some(quote_spanned!(Span::mixed_site()=> let boot_info = #fn_arg; )),
+            ),
+            None => (None, None),
+        };
+        let params = fn_param.iter().chain(boot_info_param.iter());
+        let args = arg_ident.iter().chain(boot_info_ident.iter());
 
         quote!(
             #[export_name = "main"]
             #(#attrs)*
             pub #unsafety fn #hash() -> ! {
                 #[inline(always)]
-                #unsafety fn #hash<'a>(#fn_param) -> ! {
+                #unsafety fn #hash<'a>(#(#params),*) -> ! {
                     #(#vars)*
                     #(#stmts)*
                 }
+                #boot_info_def
                 #arg_def
-                { #hash(#arg_ident) }
+                { #hash(#(#args),*) }
             }
         )
         .into()
@@ -237,7 +274,8 @@ pub fn entry(args: TokenStream, input: TokenStream) -> TokenStream {
         let err = match interrupt_enable {
             None => parse::Error::new(
                 f.sig.span(),
-                "`#[entry]` function must have signature `[unsafe] fn([<ident> : CriticalSection]) -> !`",
+                "`#[entry]` function must have signature `[unsafe] fn([<ident> : CriticalSection], \
+                 [<ident> : BootInfo]) -> !`",
             ),
             Some(EntryInterruptEnable { pre_interrupt: None }) => parse::Error::new(
                 f.sig.span(),
@@ -378,6 +416,18 @@ impl EntryInterruptEnable {
 /// `#[interrupt] fn DefaultHandler(..` can be used to override the default interrupt handler. When
 /// not overridden `DefaultHandler` defaults to an infinite loop.
 ///
+/// The `DefaultHandler` can also take the index of the vector that fired as its first argument,
+/// i.e. `[unsafe] fn(<name>: u8, [<name>: CriticalSection]) [-> !]`, which helps tracking down
+/// spurious interrupts. The index is 0 for the vector at `ORIGIN(VECTORS)` and goes up by one per
+/// vector. It's passed by a small trampoline per vector: every vector has one when the `device`
+/// feature is disabled, otherwise the vectors a device crate binds to `__DefaultHandler<n>` instead
+/// of `DefaultHandler` do. Vectors that are bound straight to `DefaultHandler` pass `0xff`, and
+/// svd2rust device crates bind all of them that way, so with those the index is always `0xff`. The
+/// trampolines add about 50 cycles to the interrupt latency, and `wake_cpu` can't be used with this
+/// form. They save the registers that the handler may clobber. With the `upper-memory` feature of
+/// `msp430-rt` they save all 20 bits of r4 to r15, because the handler itself only saves the lower
+/// 16 bits of the registers it uses.
+///
 /// `#[interrupt(wake_cpu)]` additionally returns the CPU to active mode after the interrupt
 /// returns. This cannot be done by naively writing to the status register, as the status register
 /// contents are pushed to the stack before an interrupt begins and this value is loaded back into
@@ -387,6 +437,13 @@ impl EntryInterruptEnable {
 /// handler begins.
 /// The following status register bits are cleared: SCG1, SCG0, OSC_OFF and CPU_OFF.
 ///
+/// Vector table entries are 16-bit addresses, so handlers are placed in a `.lower.text` section,
+/// which stays below 0x10000 even when the `upper-memory` feature of `msp430-rt` is enabled. The
+/// handlers only save the lower 16 bits of the registers they use, so they mustn't interrupt code
+/// that keeps 20-bit addresses in r4 to r10, like the code in the `.upper` sections.
+/// Handlers with a `#[link_section]` or a `#[ramfunc]` attribute are left where they're put, except
+/// that `.upper` sections are rejected.
+///
 /// # Properties
 ///
 /// Interrupts handlers can only be called by the hardware. Other parts of the program can't refer
@@ -464,6 +521,51 @@ pub fn interrupt(args: TokenStream, input: TokenStream) -> TokenStream {
     let stmts = block.stmts;
     let unsafety = f.sig.unsafety;
 
+    // The body of a `wake_cpu` handler is a separate function that the naked handler jumps to, so
+    // it has to be placed in the same section as the handler. Naked functions can't have
+    // `#[inline]` attributes, nor attribute macros, so `#[ramfunc]` is expanded here.
+    let ramfunc = if attrs.iter().any(is_ramfunc) {
+        let section = format!(".ramtext.{}", ident);
+        Some(quote!(#[link_section = #section]))
+    } else {
+        None
+    };
+    let placement = attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("link_section"))
+        .collect::<Vec<_>>();
+    // The upper-memory feature of msp430-rt places .upper sections above 0x10000, out of reach of
+    // the vector table
+    for attr in &placement {
+        if let Ok(Meta::NameValue(MetaNameValue {
+            lit: Lit::Str(section),
+            ..
+        })) = attr.parse_meta()
+        {
+            if section.value().starts_with(".upper") {
+                return parse::Error::new(
+                    section.span(),
+                    "interrupt handlers can't be placed in an `.upper` section, which can be above \
+                     0x10000, out of reach of the 16-bit interrupt vectors",
+                )
+                .to_compile_error()
+                .into();
+            }
+        }
+    }
+    let naked_attrs = attrs
+        .iter()
+        .filter(|attr| !attr.path.is_ident("inline") && !is_ramfunc(attr))
+        .collect::<Vec<_>>();
+    // Vector table entries are 16 bits wide, so handlers that aren't placed explicitly go in a
+    // .lower section, which link.x keeps below 0x10000 even with the `upper-memory` feature
+    let lower = if ramfunc.is_none() && placement.is_empty() {
+        let section = format!(".lower.text.{}", ident);
+        Some(quote!(#[link_section = #section]))
+    } else {
+        None
+    };
+
     let valid_signature = f.sig.constness.is_none()
         && f.vis == Visibility::Inherited
         && f.sig.abi.is_none()
@@ -479,7 +581,22 @@ pub fn interrupt(args: TokenStream, input: TokenStream) -> TokenStream {
             },
         };
 
-    let pair = extract_critical_section_arg(&f.sig.inputs);
+    // `DefaultHandler` can take the index of the vector that fired as its first argument
+    let (inputs, irq_param) = if ident == "DefaultHandler" {
+        extract_irq_arg(&f.sig.inputs)
+    } else {
+        (f.sig.inputs.clone(), None)
+    };
+    if irq_param.is_some() && wake_cpu {
+        return parse::Error::new(
+            Span::call_site(),
+            "`wake_cpu` is not supported on a `DefaultHandler` that takes the interrupt index",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let pair = extract_critical_section_arg(&inputs);
 
     if let (true, Ok(ParamArgPair { fn_arg, fn_param })) = (valid_signature, pair) {
         let (statics, stmts) = match extract_static_muts(stmts) {
@@ -513,10 +630,15 @@ pub fn interrupt(args: TokenStream, input: TokenStream) -> TokenStream {
         if wake_cpu {
             quote!(
                 #[export_name = #ident]
-                #(#attrs)*
+                #(#naked_attrs)*
+                #ramfunc
+                #lower
                 #[unsafe(naked)]
                 unsafe extern "msp430-interrupt" fn #hash() {
                     #[inline(always)]
+                    #(#placement)*
+                    #ramfunc
+                    #lower
                     #unsafety extern "msp430-interrupt" fn #hash<'a>(#fn_param) #output {
                         #check
                         #(#vars)*
@@ -534,10 +656,42 @@ pub fn interrupt(args: TokenStream, input: TokenStream) -> TokenStream {
                     }
                 }
             )
+        } else if let Some(irq_param) = irq_param {
+            let irq = Ident::new("irq", Span::mixed_site());
+            let params = Some(irq_param).into_iter().chain(fn_param);
+            let shim = random_ident();
+            // The shim stays alone in `.lower.text.DefaultHandler`, which link.x places at a fixed
+            // address
+            let lower_irq = lower
+                .as_ref()
+                .map(|_| quote!(#[link_section = ".lower.text.DefaultHandlerIrq"]));
+            quote!(
+                // Called by the per-vector trampolines of msp430-rt
+                #[export_name = "DefaultHandlerIrq"]
+                #(#attrs)*
+                #lower_irq
+                #unsafety extern "C" fn #hash(#irq: u8) #output {
+                    #[inline(always)]
+                    #unsafety fn #hash<'a>(#(#params),*) #output {
+                        #(#vars)*
+                        #(#stmts)*
+                    }
+                    { #hash(#irq, #fn_arg) }
+                }
+
+                // Vectors that don't go through a trampoline, e.g. the ones a PAC binds straight
+                // to `DefaultHandler`, can't tell which interrupt fired
+                #[export_name = "DefaultHandler"]
+                #lower
+                unsafe extern "msp430-interrupt" fn #shim() {
+                    #hash(0xff);
+                }
+            )
         } else {
             quote!(
                 #[export_name = #ident]
                 #(#attrs)*
+                #lower
                 #unsafety extern "msp430-interrupt" fn #hash() {
                     #check
 
@@ -549,11 +703,21 @@ pub fn interrupt(args: TokenStream, input: TokenStream) -> TokenStream {
                     { #hash(#fn_arg) }
                 }
             )
-        }.into()
+        }
+        .into()
+    } else if ident == "DefaultHandler" {
+        parse::Error::new(
+            fspan,
+            "`#[interrupt]` handlers must have signature `[unsafe] fn([<name>: u8], \
+             [<name>: CriticalSection]) [-> !]` when overriding the `DefaultHandler`",
+        )
+        .to_compile_error()
+        .into()
     } else {
         parse::Error::new(
             fspan,
-            "`#[interrupt]` handlers must have signature `[unsafe] fn([<name>: CriticalSection]) [-> !]`",
+            "`#[interrupt]` handlers must have signature \
+             `[unsafe] fn([<name>: CriticalSection]) [-> !]`",
         )
         .to_compile_error()
         .into()
@@ -585,7 +749,97 @@ pub fn pre_init(args: TokenStream, input: TokenStream) -> TokenStream {
     let f = parse_macro_input!(input as ItemFn);
 
     // check the function signature
-    let valid_signature = f.sig.constness.is_none()
+    if !is_unsafe_fn_unit(&f) {
+        return parse::Error::new(
+            f.sig.span(),
+            "`#[pre_init]` function must have signature `unsafe fn()`",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    if !args.is_empty() {
+        return parse::Error::new(Span::call_site(), "this attribute accepts no arguments")
+            .to_compile_error()
+            .into();
+    }
+
+    // XXX should we blacklist other attributes?
+    let attrs = f.attrs;
+    let ident = f.sig.ident;
+    let block = f.block;
+
+    quote!(
+        #[export_name = "__pre_init"]
+        #(#attrs)*
+        pub unsafe fn #ident() #block
+    )
+    .into()
+}
+
+/// Attribute to declare the function that handles a wake-up from LPMx.5.
+///
+/// **IMPORTANT**: This attribute can appear at most *once* in the dependency graph.
+///
+/// The function must have the signature of `unsafe fn()`.
+///
+/// A wake-up from LPM3.5 or LPM4.5 goes through the reset vector. When `Reset` finds out from
+/// `SYSRSTIV` that this is such a wake-up, it calls this function right after holding the
+/// watchdog, before `PreInit` and before static variables are initialized. Any access of static
+/// variables, other than `#[uninit]` and `#[persistent]` ones, will result in undefined behavior.
+///
+/// The function typically restores the I/O configuration, clears `LOCKLPM5` and handles the event
+/// that woke the device up. It can then enter LPMx.5 again, in which case it never returns, or
+/// return to carry on with a normal boot. The entry point can tell the latter from other resets
+/// with `msp430_rt::BootInfo::is_lpm5_wakeup`.
+///
+/// `msp430-rt` only re-exports this attribute with its `lpm5-wakeup` feature, and linking fails if
+/// `memory.x` doesn't set `__SYSRSTIV_ADDR`.
+///
+/// ## Examples
+///
+/// ``` ignore
+/// use msp430_rt_macros::lpm5_wakeup;
+///
+/// #[lpm5_wakeup]
+/// unsafe fn wakeup() {
+///     // restore the I/O configuration, clear LOCKLPM5, ..
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn lpm5_wakeup(args: TokenStream, input: TokenStream) -> TokenStream {
+    let f = parse_macro_input!(input as ItemFn);
+
+    if !is_unsafe_fn_unit(&f) {
+        return parse::Error::new(
+            f.sig.span(),
+            "`#[lpm5_wakeup]` function must have signature `unsafe fn()`",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    if !args.is_empty() {
+        return parse::Error::new(Span::call_site(), "this attribute accepts no arguments")
+            .to_compile_error()
+            .into();
+    }
+
+    let attrs = f.attrs;
+    let ident = f.sig.ident;
+    let block = f.block;
+
+    quote!(
+        #[export_name = "Lpm5Wakeup"]
+        #(#attrs)*
+        pub unsafe fn #ident() #block
+    )
+    .into()
+}
+
+// `unsafe fn()`, private and without generics
+fn is_unsafe_fn_unit(f: &ItemFn) -> bool {
+    f.sig.constness.is_none()
         && f.vis == Visibility::Inherited
         && f.sig.unsafety.is_some()
         && f.sig.abi.is_none()
@@ -599,12 +853,92 @@ pub fn pre_init(args: TokenStream, input: TokenStream) -> TokenStream {
                 Type::Tuple(ref tuple) => tuple.elems.is_empty(),
                 _ => false,
             },
-        };
+        }
+}
+
+/// Attribute to run a function from RAM.
+///
+/// The function is placed in the `.ramtext` section, which the reset handler copies from ROM to RAM
+/// right after initializing `.data`. This is required for code that erases or writes flash, and
+/// speeds up hot code on FRAM devices running with FRAM wait states.
+///
+/// The function is never inlined, since inlining would move its body back into ROM. Any function
+/// it calls still runs from wherever that function lives.
+///
+/// The attribute can be combined with `#[interrupt]` to run an interrupt handler from RAM.
+///
+/// ## Examples
+///
+/// ``` ignore
+/// use msp430_rt_macros::ramfunc;
+///
+/// #[ramfunc]
+/// fn erase_segment(addr: *mut u16) {
+///     // ..
+/// }
+///
+/// #[interrupt]
+/// #[ramfunc]
+/// fn TIMER0_A0() {
+///     // ..
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn ramfunc(args: TokenStream, input: TokenStream) -> TokenStream {
+    let f = parse_macro_input!(input as ItemFn);
+
+    if !args.is_empty() {
+        return parse::Error::new(Span::call_site(), "this attribute accepts no arguments")
+            .to_compile_error()
+            .into();
+    }
+
+    let section = format!(".ramtext.{}", f.sig.ident);
+
+    quote!(
+        #[link_section = #section]
+        #[inline(never)]
+        #f
+    )
+    .into()
+}
+
+// Checks whether `attr` is `#[ramfunc]`, optionally behind a path like `msp430_rt::`
+fn is_ramfunc(attr: &Attribute) -> bool {
+    attr.path
+        .segments
+        .last()
+        .is_some_and(|seg| seg.ident == "ramfunc")
+}
+
+/// Attribute to place a `static mut` variable in the `.uninit` section.
+///
+/// The reset handler neither zeroes nor initializes this section, so the variable keeps whatever
+/// RAM contained before the reset; after a power cycle its contents are garbage. This is useful for
+/// crash logs, reset counters, and large buffers that don't need to be zeroed at startup.
+///
+/// The variable must be a `static mut` of type `MaybeUninit<T>`. Its initializer is ignored;
+/// `MaybeUninit::uninit()` is the only one that makes sense.
+///
+/// ## Examples
+///
+/// ``` no_run
+/// # use msp430_rt_macros::uninit;
+/// use core::mem::MaybeUninit;
+///
+/// #[uninit]
+/// static mut RESET_COUNT: MaybeUninit<u16> = MaybeUninit::uninit();
+///
+/// # fn main() {}
+/// ```
+#[proc_macro_attribute]
+pub fn uninit(args: TokenStream, input: TokenStream) -> TokenStream {
+    let var = parse_macro_input!(input as ItemStatic);
 
-    if !valid_signature {
+    if var.mutability.is_none() || !is_maybe_uninit(&var.ty) {
         return parse::Error::new(
-            f.sig.span(),
-            "`#[pre_init]` function must have signature `unsafe fn()`",
+            var.span(),
+            "`#[uninit]` must be applied to a `static mut` of type `MaybeUninit<T>`",
         )
         .to_compile_error()
         .into();
@@ -616,19 +950,176 @@ pub fn pre_init(args: TokenStream, input: TokenStream) -> TokenStream {
             .into();
     }
 
-    // XXX should we blacklist other attributes?
-    let attrs = f.attrs;
-    let ident = f.sig.ident;
-    let block = f.block;
+    let section = format!(".uninit.{}", var.ident);
 
     quote!(
-        #[export_name = "__pre_init"]
-        #(#attrs)*
-        pub unsafe fn #ident() #block
+        #[link_section = #section]
+        #var
+    )
+    .into()
+}
+
+/// Attribute to place a `static` variable in the `.persistent` section.
+///
+/// On FRAM devices, variables in this section keep their value across resets and power cycles.
+/// Their initializer is written to FRAM when the device is programmed; the reset handler never
+/// initializes them again. This is the equivalent of TI's `#pragma PERSISTENT`.
+///
+/// Use `msp430_rt::fram::Persistent` as the type of the variable to get a safe API that lifts the
+/// FRAM write protection around writes.
+///
+/// ## Examples
+///
+/// ``` ignore
+/// use msp430_rt::fram::Persistent;
+/// use msp430_rt_macros::persistent;
+///
+/// #[persistent]
+/// static BOOT_COUNT: Persistent<u16> = Persistent::new(0);
+/// ```
+#[proc_macro_attribute]
+pub fn persistent(args: TokenStream, input: TokenStream) -> TokenStream {
+    let var = parse_macro_input!(input as ItemStatic);
+
+    if !args.is_empty() {
+        return parse::Error::new(Span::call_site(), "this attribute accepts no arguments")
+            .to_compile_error()
+            .into();
+    }
+
+    let section = format!(".persistent.{}", var.ident);
+
+    quote!(
+        #[link_section = #section]
+        #var
+    )
+    .into()
+}
+
+/// Attribute to place a `static` variable in a segment of the information memory.
+///
+/// The information memory is a small block of flash or FRAM, divided in segments `A` to `D`, that
+/// is usually set aside for calibration and configuration data. The variable goes in the `.infoA`,
+/// `.infoB`, `.infoC` or `.infoD` section, which is only placed when the `info-mem` feature of
+/// `msp430-rt` is enabled. Like `.persistent`, these sections are written when the device is
+/// programmed and never initialized by the reset handler, and the variable is kept even if the
+/// program doesn't use it.
+///
+/// On most devices segment `A` holds factory calibration data and is locked. The `info-mem`
+/// documentation of `msp430-rt` explains how to make sure that the program leaves it untouched.
+///
+/// ## Examples
+///
+/// ``` ignore
+/// use msp430_rt_macros::info_mem;
+///
+/// #[info_mem(segment = "B")]
+/// static NODE_ADDRESS: u16 = 0x0042;
+/// ```
+#[proc_macro_attribute]
+pub fn info_mem(args: TokenStream, input: TokenStream) -> TokenStream {
+    let var = parse_macro_input!(input as ItemStatic);
+    let args = parse_macro_input!(args as AttributeArgs);
+
+    let segment = match &args[..] {
+        [NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+            path,
+            lit: Lit::Str(segment),
+            ..
+        }))] if path.is_ident("segment") => segment,
+        _ => {
+            return parse::Error::new(
+                Span::call_site(),
+                "this attribute must be used as `#[info_mem(segment = \"<A, B, C or D>\")]`",
+            )
+            .to_compile_error()
+            .into()
+        }
+    };
+    if !["A", "B", "C", "D"].contains(&segment.value().as_str()) {
+        return parse::Error::new(
+            segment.span(),
+            "the information memory segment must be \"A\", \"B\", \"C\" or \"D\"",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let section = format!(".info{}.{}", segment.value(), var.ident);
+
+    quote!(
+        #[link_section = #section]
+        #[used]
+        #var
+    )
+    .into()
+}
+
+/// Attribute to place a `static` variable in the second RAM region.
+///
+/// Some devices have a block of RAM apart from the main one, e.g. the LEA RAM of the MSP430FR5994
+/// or the USB RAM of the MSP430F5529. With the `ram2` feature of `msp430-rt`, the variable goes in
+/// that region (`RAM2`); without it, in the main RAM. Either way the reset handler initializes it
+/// like any other static.
+///
+/// By default the variable goes in `.data2`, so its initial value takes up flash as well. A
+/// variable whose initial value is all zeros can be placed in `.bss2` with `#[ram2(zeroed)]`
+/// instead, which the reset handler zeroes. A non-zero initial value is then rejected at compile
+/// time.
+///
+/// ## Examples
+///
+/// ``` ignore
+/// use msp430_rt_macros::ram2;
+///
+/// #[ram2(zeroed)]
+/// static mut SAMPLES: [i16; 512] = [0; 512];
+///
+/// #[ram2]
+/// static mut GAIN: i16 = 0x4000;
+/// ```
+#[proc_macro_attribute]
+pub fn ram2(args: TokenStream, input: TokenStream) -> TokenStream {
+    let var = parse_macro_input!(input as ItemStatic);
+    let args = parse_macro_input!(args as AttributeArgs);
+
+    // The `.bss.` prefix makes LLVM emit a NOBITS section, and reject non-zero initial values
+    let prefix = match &args[..] {
+        [] => ".data.ram2",
+        [NestedMeta::Meta(Meta::Path(path))] if path.is_ident("zeroed") => ".bss.ram2",
+        _ => {
+            return parse::Error::new(
+                Span::call_site(),
+                "this attribute must be used as `#[ram2]` or `#[ram2(zeroed)]`",
+            )
+            .to_compile_error()
+            .into()
+        }
+    };
+
+    let section = format!("{}.{}", prefix, var.ident);
+
+    quote!(
+        #[link_section = #section]
+        #var
     )
     .into()
 }
 
+// Checks whether `ty` is `MaybeUninit<..>`, optionally behind a path like `core::mem::`
+fn is_maybe_uninit(ty: &Type) -> bool {
+    if let Type::Path(TypePath { qself: None, path }) = ty {
+        if let Some(PathSegment {
+            ident,
+            arguments: PathArguments::AngleBracketed(_),
+        }) = path.segments.last()
+        {
+            return ident == "MaybeUninit";
+        }
+    }
+    false
+}
+
 // Parses an optional `<name>: CriticalSection` from a list of function arguments.
 // Additional arguments are considered invalid
 fn extract_critical_section_arg(list: &Punctuated<FnArg, Token![,]>) -> Result<ParamArgPair, ()> {
@@ -679,6 +1170,71 @@ fn extract_critical_section_arg(list: &Punctuated<FnArg, Token![,]>) -> Result<P
     Err(())
 }
 
+// Splits an optional leading `<name>: u8`, the interrupt index taken by `DefaultHandler`, off a
+// list of function arguments
+fn extract_irq_arg(
+    list: &Punctuated<FnArg, Token![,]>,
+) -> (
+    Punctuated<FnArg, Token![,]>,
+    Option<proc_macro2::TokenStream>,
+) {
+    if let Some(FnArg::Typed(pat_type)) = list.first() {
+        if let (
+            Pat::Ident(PatIdent {
+                by_ref: None,
+                subpat: None,
+                ..
+            }),
+            Type::Path(TypePath { qself: None, path }),
+            [],
+        ) = (&*pat_type.pat, &*pat_type.ty, &*pat_type.attrs)
+        {
+            if path.is_ident("u8") {
+                let rest = list.iter().skip(1).cloned().collect();
+                return (rest, Some(quote! { #pat_type }));
+            }
+        }
+    }
+    (list.clone(), None)
+}
+
+// Splits an optional trailing `<name>: BootInfo` off a list of function arguments. The other
+// arguments are left for `extract_critical_section_arg` or `extract_init_arg` to check
+fn extract_boot_info_arg(
+    list: &Punctuated<FnArg, Token![,]>,
+) -> (Punctuated<FnArg, Token![,]>, Option<ParamArgPair>) {
+    if let Some(FnArg::Typed(pat_type)) = list.last() {
+        if let (
+            Pat::Ident(PatIdent {
+                by_ref: None,
+                subpat: None,
+                ..
+            }),
+            Type::Path(TypePath { qself: None, path }),
+            [],
+        ) = (&*pat_type.pat, &*pat_type.ty, &*pat_type.attrs)
+        {
+            if matches!(
+                path.segments.last(),
+                Some(PathSegment {
+                    ident,
+                    arguments: PathArguments::None,
+                }) if ident == "BootInfo"
+            ) {
+                let rest = list.iter().take(list.len() - 1).cloned().collect();
+                return (
+                    rest,
+                    Some(ParamArgPair {
+                        fn_param: Some(quote! { #pat_type }),
+                        fn_arg: Some(quote! { <#path>::get() }),
+                    }),
+                );
+            }
+        }
+    }
+    (list.clone(), None)
+}
+
 // Creates a random identifier
 fn random_ident() -> Ident {
     static CALL_COUNT: AtomicUsize = AtomicUsize::new(0);
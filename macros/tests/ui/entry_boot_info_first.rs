@@ -0,0 +1,10 @@
+#![no_main]
+
+use msp430_rt_macros::entry;
+
+struct BootInfo;
+
+#[entry]
+fn main(_boot: BootInfo, _cs: msp430::interrupt::CriticalSection) -> ! {
+    loop {}
+}
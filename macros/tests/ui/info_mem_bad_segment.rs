@@ -0,0 +1,11 @@
+#![no_main]
+
+use msp430_rt_macros::{entry, info_mem};
+
+#[entry]
+fn main() -> ! {
+    loop {}
+}
+
+#[info_mem(segment = "E")]
+static FOO: u16 = 0;
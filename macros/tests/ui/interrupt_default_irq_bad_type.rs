@@ -0,0 +1,6 @@
+#![no_main]
+
+use msp430_rt_macros::interrupt;
+
+#[interrupt]
+fn DefaultHandler(_irq: u16) {}
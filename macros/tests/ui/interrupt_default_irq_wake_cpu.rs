@@ -0,0 +1,6 @@
+#![no_main]
+
+use msp430_rt_macros::interrupt;
+
+#[interrupt(wake_cpu)]
+fn DefaultHandler(_irq: u8) {}
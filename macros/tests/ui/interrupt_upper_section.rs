@@ -0,0 +1,7 @@
+#![no_main]
+
+use msp430_rt_macros::interrupt;
+
+#[interrupt]
+#[link_section = ".upper.text.DefaultHandler"]
+fn DefaultHandler() {}
@@ -0,0 +1,11 @@
+#![no_main]
+
+use msp430_rt_macros::{entry, lpm5_wakeup};
+
+#[entry]
+fn bar() -> ! {
+    loop {}
+}
+
+#[lpm5_wakeup]
+fn foo() {}
@@ -0,0 +1,11 @@
+#![no_main]
+
+use msp430_rt_macros::{entry, persistent};
+
+#[entry]
+fn main() -> ! {
+    loop {}
+}
+
+#[persistent(arg)]
+static FOO: u16 = 0;
@@ -0,0 +1,11 @@
+#![no_main]
+
+use msp430_rt_macros::{entry, ram2};
+
+#[entry]
+fn main() -> ! {
+    loop {}
+}
+
+#[ram2(bss)]
+static mut FOO: u16 = 0;
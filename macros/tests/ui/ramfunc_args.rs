@@ -0,0 +1,11 @@
+#![no_main]
+
+use msp430_rt_macros::{entry, ramfunc};
+
+#[entry]
+fn main() -> ! {
+    loop {}
+}
+
+#[ramfunc(arg)]
+fn foo() {}
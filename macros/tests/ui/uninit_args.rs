@@ -0,0 +1,11 @@
+#![no_main]
+
+use msp430_rt_macros::{entry, uninit};
+
+#[entry]
+fn main() -> ! {
+    loop {}
+}
+
+#[uninit(arg)]
+static mut FOO: core::mem::MaybeUninit<u16> = core::mem::MaybeUninit::uninit();
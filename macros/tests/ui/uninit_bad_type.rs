@@ -0,0 +1,11 @@
+#![no_main]
+
+use msp430_rt_macros::{entry, uninit};
+
+#[entry]
+fn main() -> ! {
+    loop {}
+}
+
+#[uninit]
+static mut FOO: u16 = 0;
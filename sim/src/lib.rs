@@ -0,0 +1,989 @@
+//! Host-side emulator for the `msp430-rt` startup code
+//!
+//! `Reset` ships as prebuilt blobs, `bin/msp430-none-elf.a` and its MSP430X variant
+//! `bin/msp430x-none-elf.a`, so it normally only runs on a device. This crate reads the object file
+//! in such an archive, links the sections a test asks for at the addresses it picks and executes
+//! the machine code on an MSP430X CPU with a flat 1 MiB address space. Tests can then check what the blob does to RAM without hardware or an MSP430 toolchain.
+//!
+//! Linker-provided symbols (`_sbss`, `_stack_start`, ...) are supplied by the caller through
+//! [`Machine::symbol`]. Functions that live outside of the blob (e.g. `PreInit`) are supplied
+//! through [`Machine::stub`]: a call to one is recorded and returns immediately, and a branch to
+//! one (e.g. to `main`) ends the emulation.
+//!
+//! [`Object`] also reads the ELF files a host `ld` produces, which the linker script tests use to
+//! look at the outcome of `link.x`.
+
+#![cfg_attr(target_os = "none", no_std)]
+#![cfg(not(target_os = "none"))]
+
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryInto,
+    fmt, fs,
+    path::Path,
+};
+
+/// Status register bits
+const C: u32 = 1 << 0;
+const Z: u32 = 1 << 1;
+const N: u32 = 1 << 2;
+const V: u32 = 1 << 8;
+
+/// The MSP430X address space is 20 bits wide
+const ADDR_MASK: u32 = 0xf_ffff;
+
+/// Upper bound on the number of executed instructions; protects tests against runaway loops
+const MAX_STEPS: usize = 1_000_000;
+
+/// Value that a stub leaves in the registers a called function may clobber (r11 to r15)
+pub const CLOBBERED: u32 = 0xdead;
+
+/// Errors raised while reading, linking or running a program
+#[derive(Debug, PartialEq)]
+pub struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+macro_rules! bail {
+    ($($arg:tt)*) => {
+        return Err(Error(format!($($arg)*)))
+    };
+}
+
+// ELF constants
+const SHT_SYMTAB: u32 = 2;
+const SHT_RELA: u32 = 4;
+const SHT_NOBITS: u32 = 8;
+const SHF_ALLOC: u32 = 2;
+const SHN_UNDEF: u16 = 0;
+const SHN_LORESERVE: u16 = 0xff00;
+const STB_LOCAL: u8 = 0;
+const STB_WEAK: u8 = 2;
+const STT_SECTION: u8 = 3;
+
+// MSP430 relocation types
+const R_MSP430_NONE: u8 = 0;
+const R_MSP430_32: u8 = 1;
+const R_MSP430_16: u8 = 3;
+const R_MSP430_16_BYTE: u8 = 5;
+
+// Objects for the MSP430X CPU number their relocation types differently
+const EF_MSP430_MACH: u32 = 0xff;
+const E_MSP430_MACH_MSP430X: u32 = 45;
+const R_MSP430X_ABS32: u8 = 1;
+const R_MSP430X_ABS16: u8 = 2;
+const R_MSP430X_ABS16_X: u8 = 15;
+
+/// A section of an ELF file
+#[derive(Debug)]
+pub struct Section {
+    /// Name, e.g. `.Reset`
+    pub name: String,
+    /// Address; 0 in an object file
+    pub addr: u32,
+    /// Size in bytes
+    pub size: u32,
+    /// Alignment in bytes
+    pub align: u32,
+    /// Whether the section takes up memory when the program runs
+    pub alloc: bool,
+    /// Contents; empty for sections without any (`.bss`, `.uninit`, ...)
+    pub data: Vec<u8>,
+    relocs: Vec<Reloc>,
+}
+
+/// A reference to a global symbol
+#[derive(Debug)]
+pub struct Relocation {
+    /// Offset into the section
+    pub offset: u32,
+    /// Name of the symbol
+    pub symbol: String,
+    /// Added to the address of the symbol
+    pub addend: i32,
+    /// Size of the reference in bytes
+    pub size: u32,
+    /// Whether the symbol is weak, i.e. 0 if nothing defines it
+    pub weak: bool,
+}
+
+#[derive(Debug)]
+struct Reloc {
+    offset: u32,
+    symbol: usize,
+    kind: u8,
+    addend: i32,
+}
+
+#[derive(Debug)]
+struct Symbol {
+    name: String,
+    value: u32,
+    /// Index of the section the symbol is defined in. `None` for undefined and absolute symbols
+    section: Option<usize>,
+    defined: bool,
+    local: bool,
+    weak: bool,
+}
+
+/// A 32-bit little-endian ELF file: the MSP430 object file in the blob or the output of a host `ld`
+#[derive(Debug)]
+pub struct Object {
+    sections: Vec<Section>,
+    symbols: Vec<Symbol>,
+}
+
+impl Object {
+    /// Reads an ELF file, or the first ELF file in an `ar` archive
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        match fs::read(path) {
+            Ok(bytes) => Object::parse(&bytes)
+                .map_err(|Error(e)| Error(format!("{}: {}", path.display(), e))),
+            Err(e) => bail!("{}: {}", path.display(), e),
+        }
+    }
+
+    /// Parses an ELF file, or the first ELF file in an `ar` archive
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        match bytes.strip_prefix(b"!<arch>\n") {
+            Some(members) => Object::parse_elf(first_elf_member(members)?),
+            None => Object::parse_elf(bytes),
+        }
+    }
+
+    fn parse_elf(elf: &[u8]) -> Result<Self, Error> {
+        if elf.get(..6) != Some(b"\x7fELF\x01\x01") {
+            bail!("not a 32-bit little-endian ELF file");
+        }
+
+        let shoff = u32_at(elf, 0x20)? as usize;
+        let msp430x = u32_at(elf, 0x24)? & EF_MSP430_MACH == E_MSP430_MACH_MSP430X;
+        let shentsize = usize::from(u16_at(elf, 0x2e)?);
+        let shnum = usize::from(u16_at(elf, 0x30)?);
+        let shstrndx = usize::from(u16_at(elf, 0x32)?);
+
+        struct Header {
+            name: u32,
+            kind: u32,
+            flags: u32,
+            addr: u32,
+            offset: usize,
+            size: u32,
+            link: usize,
+            info: usize,
+            align: u32,
+        }
+        let headers = (0..shnum)
+            .map(|i| {
+                let h = shoff + i * shentsize;
+                Ok(Header {
+                    name: u32_at(elf, h)?,
+                    kind: u32_at(elf, h + 4)?,
+                    flags: u32_at(elf, h + 8)?,
+                    addr: u32_at(elf, h + 12)?,
+                    offset: u32_at(elf, h + 16)? as usize,
+                    size: u32_at(elf, h + 20)?,
+                    link: u32_at(elf, h + 24)? as usize,
+                    info: u32_at(elf, h + 28)? as usize,
+                    align: u32_at(elf, h + 32)?,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let contents = |h: &Header| match elf.get(h.offset..h.offset + h.size as usize) {
+            Some(bytes) => Ok(bytes),
+            None => bail!("section extends past the end of the file"),
+        };
+
+        let shstrtab = match headers.get(shstrndx) {
+            Some(h) => contents(h)?,
+            None => bail!("no section name table"),
+        };
+        let mut sections = headers
+            .iter()
+            .map(|h| {
+                Ok(Section {
+                    name: str_at(shstrtab, h.name as usize)?,
+                    addr: h.addr,
+                    size: h.size,
+                    align: h.align.max(1),
+                    alloc: h.flags & SHF_ALLOC != 0,
+                    data: if h.kind == SHT_NOBITS {
+                        vec![]
+                    } else {
+                        contents(h)?.to_vec()
+                    },
+                    relocs: vec![],
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let mut symbols = vec![];
+        if let Some(symtab) = headers.iter().find(|h| h.kind == SHT_SYMTAB) {
+            let strtab = contents(&headers[symtab.link])?;
+            for entry in contents(symtab)?.chunks_exact(16) {
+                let shndx = u16_at(entry, 14)?;
+                let info = entry[12];
+                let name = if info & 0xf == STT_SECTION {
+                    sections[usize::from(shndx)].name.clone()
+                } else {
+                    str_at(strtab, u32_at(entry, 0)? as usize)?
+                };
+                symbols.push(Symbol {
+                    name,
+                    value: u32_at(entry, 4)?,
+                    section: if shndx != SHN_UNDEF && shndx < SHN_LORESERVE {
+                        Some(usize::from(shndx))
+                    } else {
+                        None
+                    },
+                    defined: shndx != SHN_UNDEF,
+                    local: info >> 4 == STB_LOCAL,
+                    weak: info >> 4 == STB_WEAK,
+                });
+            }
+        }
+
+        for h in headers.iter().filter(|h| h.kind == SHT_RELA) {
+            for entry in contents(h)?.chunks_exact(12) {
+                let info = u32_at(entry, 4)?;
+                let kind = match (msp430x, info as u8) {
+                    (false, kind) | (true, kind @ R_MSP430_NONE) => kind,
+                    (true, R_MSP430X_ABS32) => R_MSP430_32,
+                    (true, R_MSP430X_ABS16 | R_MSP430X_ABS16_X) => R_MSP430_16,
+                    (true, kind) => bail!("unsupported MSP430X relocation type {}", kind),
+                };
+                sections[h.info].relocs.push(Reloc {
+                    offset: u32_at(entry, 0)?,
+                    symbol: (info >> 8) as usize,
+                    kind,
+                    addend: u32_at(entry, 8)? as i32,
+                });
+            }
+        }
+
+        Ok(Object { sections, symbols })
+    }
+
+    /// The section called `name`
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|s| s.name == name)
+    }
+
+    /// All the sections, in file order. The first one is the null section
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    /// Value of the symbol `name`: an address in a linked file, an offset into the symbol's section
+    /// in an object file
+    pub fn symbol(&self, name: &str) -> Option<u32> {
+        self.symbols
+            .iter()
+            .find(|s| s.defined && s.name == name)
+            .map(|s| s.value)
+    }
+
+    /// Names and offsets of the global symbols defined in `section`
+    pub fn globals(&self, section: &str) -> Vec<(&str, u32)> {
+        self.symbols
+            .iter()
+            .filter(|s| {
+                !s.local && s.section.map(|i| self.sections[i].name.as_str()) == Some(section)
+            })
+            .map(|s| (s.name.as_str(), s.value))
+            .collect()
+    }
+
+    /// Relocations of `section` against global symbols, in offset order
+    pub fn relocations(&self, section: &str) -> Vec<Relocation> {
+        let mut relocations = self
+            .sections
+            .iter()
+            .filter(|s| s.name == section)
+            .flat_map(|s| &s.relocs)
+            .filter(|r| !self.symbols[r.symbol].local)
+            .map(|r| Relocation {
+                offset: r.offset,
+                symbol: self.symbols[r.symbol].name.clone(),
+                addend: r.addend,
+                size: if r.kind == R_MSP430_32 { 4 } else { 2 },
+                weak: self.symbols[r.symbol].weak,
+            })
+            .collect::<Vec<_>>();
+        relocations.sort_by_key(|r| r.offset);
+        relocations
+    }
+
+    /// Reads a little-endian word at `addr` in a linked file
+    pub fn word(&self, addr: u32) -> Option<u16> {
+        self.sections.iter().find_map(|s| {
+            let offset = addr.checked_sub(s.addr)? as usize;
+            let bytes = s.data.get(offset..offset + 2)?;
+            Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+        })
+    }
+}
+
+fn first_elf_member(mut members: &[u8]) -> Result<&[u8], Error> {
+    while members.len() >= 60 {
+        let size = std::str::from_utf8(&members[48..58])
+            .ok()
+            .and_then(|s| s.trim().parse::<usize>().ok());
+        let data = match size.and_then(|size| members.get(60..60 + size)) {
+            Some(data) => data,
+            None => bail!("malformed archive member header"),
+        };
+        if data.starts_with(b"\x7fELF") {
+            return Ok(data);
+        }
+        // Members are 2-byte aligned
+        members = &members[(60 + data.len() + 1) & !1..];
+    }
+    bail!("no ELF file in the archive")
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> Result<u16, Error> {
+    match bytes.get(at..at + 2) {
+        Some(b) => Ok(u16::from_le_bytes(b.try_into().unwrap())),
+        None => bail!("truncated ELF file"),
+    }
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> Result<u32, Error> {
+    match bytes.get(at..at + 4) {
+        Some(b) => Ok(u32::from_le_bytes(b.try_into().unwrap())),
+        None => bail!("truncated ELF file"),
+    }
+}
+
+fn str_at(table: &[u8], at: usize) -> Result<String, Error> {
+    let bytes = match table.get(at..) {
+        Some(bytes) => bytes,
+        None => bail!("string table index out of range"),
+    };
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    Ok(String::from_utf8_lossy(&bytes[..end]).into_owned())
+}
+
+/// Something observable the program did
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// Called a stub
+    Call(String),
+    /// Wrote a byte or a word to memory
+    Write(u32),
+}
+
+/// Why an emulation stopped
+#[derive(Debug, PartialEq)]
+pub enum Exit {
+    /// The program branched to a stub
+    Branch(String),
+    /// The program returned from an interrupt
+    Reti,
+}
+
+/// Where an operand lives once its addressing mode has been resolved
+#[derive(Clone, Copy)]
+enum Loc {
+    Reg(usize),
+    Mem(u32),
+    Const(u32),
+}
+
+/// Operand width
+#[derive(Clone, Copy, PartialEq)]
+enum Width {
+    Byte,
+    Word,
+    Addr,
+}
+
+impl Width {
+    fn mask(self) -> u32 {
+        match self {
+            Width::Byte => 0xff,
+            Width::Word => 0xffff,
+            Width::Addr => ADDR_MASK,
+        }
+    }
+
+    fn sign(self) -> u32 {
+        (self.mask() >> 1) + 1
+    }
+}
+
+/// CPU registers plus a flat 1 MiB address space
+pub struct Machine {
+    /// `r0` (PC) to `r15`, 20 bits each
+    pub regs: [u32; 16],
+    /// The whole 20-bit address space
+    pub mem: Vec<u8>,
+    /// Calls to stubs and memory writes, in program order
+    pub events: Vec<Event>,
+    /// Value of `r12`, the first argument, at each call to a stub
+    pub call_args: Vec<u16>,
+    /// Number of instructions executed so far
+    pub steps: usize,
+    symbols: HashMap<String, u32>,
+    stubs: HashMap<u32, String>,
+    // Stubs that also truncate the registers they save
+    stubs_16: HashSet<u32>,
+}
+
+impl Default for Machine {
+    fn default() -> Self {
+        Machine::new()
+    }
+}
+
+impl Machine {
+    /// Creates a machine with all registers and memory set to zero
+    pub fn new() -> Self {
+        Machine {
+            regs: [0; 16],
+            mem: vec![0; ADDR_MASK as usize + 1],
+            events: vec![],
+            call_args: vec![],
+            steps: 0,
+            symbols: HashMap::new(),
+            stubs: HashMap::new(),
+            stubs_16: HashSet::new(),
+        }
+    }
+
+    /// Defines a linker-provided symbol
+    pub fn symbol(&mut self, name: &str, value: u32) -> &mut Self {
+        self.symbols.insert(name.to_string(), value);
+        self
+    }
+
+    /// Defines a function at `addr` that only records being called
+    pub fn stub(&mut self, name: &str, addr: u32) -> &mut Self {
+        self.stubs.insert(addr, name.to_string());
+        self.symbol(name, addr)
+    }
+
+    /// Same as [`Machine::stub`], for a function built for the 16-bit MSP430 like a Rust
+    /// `#[interrupt]`: it saves and restores r4 to r10 with 16-bit `push` and `pop`, so only their
+    /// lower 16 bits survive the call
+    pub fn stub_16(&mut self, name: &str, addr: u32) -> &mut Self {
+        self.stubs_16.insert(addr);
+        self.stub(name, addr)
+    }
+
+    /// Places `sections` of `object` one after the other from `addr`, as ld would, and applies
+    /// their relocations. Every symbol they refer to must be defined by then: in one of `sections`,
+    /// by an earlier call or with [`Machine::symbol`], except for weak symbols, which are 0 then.
+    /// Returns the address right after the last section
+    pub fn link(&mut self, object: &Object, addr: u32, sections: &[&str]) -> Result<u32, Error> {
+        let mut placed = HashMap::new();
+        let mut next = addr;
+        for &name in sections {
+            let index = match object.sections.iter().position(|s| s.name == name) {
+                Some(index) => index,
+                None => bail!("no section `{}`", name),
+            };
+            let section = &object.sections[index];
+            let start = align(next, section.align);
+            let data = &section.data;
+            self.mem[start as usize..start as usize + data.len()].copy_from_slice(data);
+            placed.insert(index, start);
+            next = start + section.size;
+        }
+
+        for symbol in &object.symbols {
+            match symbol.section.and_then(|s| placed.get(&s)) {
+                Some(&start) if !symbol.local && !symbol.name.is_empty() => {
+                    self.symbols
+                        .insert(symbol.name.clone(), start + symbol.value);
+                }
+                _ => {}
+            }
+        }
+
+        for (&index, &start) in &placed {
+            for reloc in &object.sections[index].relocs {
+                let symbol = &object.symbols[reloc.symbol];
+                let target = match symbol.section.map(|s| placed.get(&s)) {
+                    Some(Some(&section)) => section + symbol.value,
+                    Some(None) if symbol.local => bail!(
+                        "`{}` refers to `{}`, which isn't linked along with it",
+                        object.sections[index].name,
+                        symbol.name
+                    ),
+                    // Absolute
+                    None if symbol.defined => symbol.value,
+                    // Undefined, or defined in a section that's placed elsewhere or not at all
+                    _ => match self.symbols.get(&symbol.name) {
+                        Some(&value) => value,
+                        None if symbol.weak => 0,
+                        None => bail!("undefined symbol `{}`", symbol.name),
+                    },
+                };
+                let value = target.wrapping_add(reloc.addend as u32);
+                let at = (start + reloc.offset) as usize;
+                match reloc.kind {
+                    R_MSP430_NONE => {}
+                    R_MSP430_16 | R_MSP430_16_BYTE => {
+                        if value > 0xffff {
+                            bail!("`{}` ({:#x}) doesn't fit in 16 bits", symbol.name, value);
+                        }
+                        self.mem[at..at + 2].copy_from_slice(&(value as u16).to_le_bytes());
+                    }
+                    R_MSP430_32 => self.mem[at..at + 4].copy_from_slice(&value.to_le_bytes()),
+                    kind => bail!(
+                        "unsupported relocation type {} in `{}`",
+                        kind,
+                        object.sections[index].name
+                    ),
+                }
+            }
+        }
+
+        Ok(next)
+    }
+
+    /// Copies `bytes` into memory, starting at `addr`
+    pub fn load(&mut self, addr: u32, bytes: &[u8]) -> &mut Self {
+        let addr = addr as usize;
+        self.mem[addr..addr + bytes.len()].copy_from_slice(bytes);
+        self
+    }
+
+    /// Names of the stubs called so far, in call order
+    pub fn calls(&self) -> Vec<&str> {
+        self.events
+            .iter()
+            .filter_map(|e| match e {
+                Event::Call(name) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Addresses of every memory write, in program order
+    pub fn writes(&self) -> Vec<u32> {
+        self.events
+            .iter()
+            .filter_map(|e| match e {
+                Event::Write(addr) => Some(*addr),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Reads a little-endian word
+    pub fn word(&self, addr: u32) -> u16 {
+        let addr = (addr & ADDR_MASK & !1) as usize;
+        u16::from_le_bytes([self.mem[addr], self.mem[addr + 1]])
+    }
+
+    /// Runs the program from the symbol `entry` until it branches to a stub or returns from an
+    /// interrupt
+    pub fn run(&mut self, entry: &str) -> Result<Exit, Error> {
+        self.regs[0] = match self.symbols.get(entry) {
+            Some(&addr) => addr,
+            None => bail!("symbol `{}` not found", entry),
+        };
+
+        loop {
+            let pc = self.regs[0];
+            if let Some(name) = self.stubs.get(&pc) {
+                return Ok(Exit::Branch(name.clone()));
+            }
+            self.steps += 1;
+            if self.steps > MAX_STEPS {
+                bail!("{:#07x}: step limit exceeded", pc);
+            }
+
+            match self.step() {
+                Ok(None) => {}
+                Ok(Some(exit)) => return Ok(exit),
+                Err(Error(e)) => bail!("{:#07x}: {}", pc, e),
+            }
+        }
+    }
+
+    fn step(&mut self) -> Result<Option<Exit>, Error> {
+        let insn = self.fetch();
+        match insn >> 12 {
+            0x0 => self.address_insn(insn)?,
+            0x1 => return self.single_operand(insn),
+            0x2 | 0x3 => self.jump(insn),
+            _ => self.double_operand(insn)?,
+        }
+        Ok(None)
+    }
+
+    // Format I: `op src, dst`
+    fn double_operand(&mut self, insn: u16) -> Result<(), Error> {
+        let width = if insn & 0x40 != 0 {
+            Width::Byte
+        } else {
+            Width::Word
+        };
+        let src = self.source(((insn >> 8) & 0xf) as usize, (insn >> 4) & 3, width);
+        let dst = self.destination((insn & 0xf) as usize, (insn >> 7) & 1);
+        let s = self.read(src, width);
+
+        let result = match insn >> 12 {
+            0x4 => Some(s),
+            0x5 => Some(self.add(self.read(dst, width), s, 0, width)),
+            0x6 => Some(self.add(self.read(dst, width), s, self.regs[2] & C, width)),
+            0x7 => Some(self.add(self.read(dst, width), !s, self.regs[2] & C, width)),
+            0x8 => Some(self.add(self.read(dst, width), !s, 1, width)),
+            0x9 => {
+                self.add(self.read(dst, width), !s, 1, width);
+                None
+            }
+            0xa => bail!("`dadd` is not supported"),
+            0xb => {
+                let r = self.read(dst, width) & s;
+                self.logic_flags(r, false, width);
+                None
+            }
+            0xc => Some(self.read(dst, width) & !s),
+            0xd => Some(self.read(dst, width) | s),
+            0xe => {
+                let d = self.read(dst, width);
+                let r = d ^ s;
+                self.logic_flags(r, d & s & width.sign() != 0, width);
+                Some(r)
+            }
+            _ => {
+                let r = self.read(dst, width) & s;
+                self.logic_flags(r, false, width);
+                Some(r)
+            }
+        };
+
+        match result {
+            Some(r) => self.write(dst, r, width),
+            None => Ok(()),
+        }
+    }
+
+    // Format II, `reti`, `pushm` and `popm`
+    fn single_operand(&mut self, insn: u16) -> Result<Option<Exit>, Error> {
+        if insn == 0x1300 {
+            // reti. The upper nibble of the saved SR holds bits 19:16 of the PC
+            let sr = self.pop(Width::Word)?;
+            let pc = self.pop(Width::Word)?;
+            self.regs[2] = sr & 0x0fff;
+            self.regs[0] = pc | (sr & 0xf000) << 4;
+            return Ok(Some(Exit::Reti));
+        }
+
+        if insn >= 0x1400 {
+            // pushm.a (0x14), pushm.w (0x15), popm.a (0x16) and popm.w (0x17)
+            let width = if insn & 0x0100 != 0 {
+                Width::Word
+            } else {
+                Width::Addr
+            };
+            let n = usize::from((insn >> 4) & 0xf) + 1;
+            let r = usize::from(insn & 0xf);
+            if insn & 0x0200 == 0 {
+                for reg in (r + 1 - n..=r).rev() {
+                    self.push(self.regs[reg], width)?;
+                }
+            } else {
+                for reg in r..r + n {
+                    self.regs[reg] = self.pop(width)?;
+                }
+            }
+            return Ok(None);
+        }
+
+        let width = if insn & 0x40 != 0 {
+            Width::Byte
+        } else {
+            Width::Word
+        };
+        let operand = self.source(usize::from(insn & 0xf), (insn >> 4) & 3, width);
+        match (insn >> 7) & 7 {
+            // rrc, rra
+            op @ 0 | op @ 2 => {
+                let v = self.read(operand, width);
+                let msb = if op == 0 {
+                    if self.regs[2] & C != 0 {
+                        width.sign()
+                    } else {
+                        0
+                    }
+                } else {
+                    v & width.sign()
+                };
+                let r = v >> 1 | msb;
+                self.set_flags(r, v & 1 != 0, false, width);
+                self.write(operand, r, width)?;
+            }
+            // swpb
+            1 => {
+                let v = self.read(operand, Width::Word);
+                self.write(operand, (v >> 8 | v << 8) & 0xffff, Width::Word)?;
+            }
+            // sxt
+            3 => {
+                let v = self.read(operand, Width::Word) as u8 as i8 as i16 as u16;
+                self.set_flags(u32::from(v), v != 0, false, Width::Word);
+                self.write(operand, u32::from(v), Width::Word)?;
+            }
+            4 => {
+                let v = self.read(operand, width);
+                self.push(v, Width::Word)?;
+            }
+            5 => {
+                let target = self.read(operand, Width::Word);
+                match self.stubs.get(&target) {
+                    Some(name) => {
+                        // A stub returns right away, and clobbers what a real function may
+                        self.events.push(Event::Call(name.clone()));
+                        self.call_args.push(self.regs[12] as u16);
+                        for reg in &mut self.regs[11..] {
+                            *reg = CLOBBERED;
+                        }
+                        if self.stubs_16.contains(&target) {
+                            for reg in &mut self.regs[4..11] {
+                                *reg &= 0xffff;
+                            }
+                        }
+                    }
+                    None => {
+                        self.push(self.regs[0], Width::Word)?;
+                        self.regs[0] = target;
+                    }
+                }
+            }
+            _ => bail!("unsupported instruction {:#06x}", insn),
+        }
+        Ok(None)
+    }
+
+    // Conditional and unconditional jumps
+    fn jump(&mut self, insn: u16) {
+        let sr = self.regs[2];
+        let (n, v) = (sr & N != 0, sr & V != 0);
+        let taken = match (insn >> 10) & 7 {
+            0 => sr & Z == 0, // jne
+            1 => sr & Z != 0, // jeq
+            2 => sr & C == 0, // jlo
+            3 => sr & C != 0, // jhs
+            4 => n,           // jn
+            5 => n == v,      // jge
+            6 => n != v,      // jl
+            _ => true,        // jmp
+        };
+        if taken {
+            // The offset is a signed 10-bit number of words
+            let offset = (((insn & 0x3ff) << 6) as i16 >> 6) as i32 * 2;
+            self.regs[0] = (self.regs[0] as i32 + offset) as u32 & ADDR_MASK;
+        }
+    }
+
+    // The MSP430X address instructions (`mova`, `cmpa`, `adda` and `suba`)
+    fn address_insn(&mut self, insn: u16) -> Result<(), Error> {
+        let src = usize::from((insn >> 8) & 0xf);
+        let dst = usize::from(insn & 0xf);
+        let imm20 = |m: &mut Machine| (src as u32) << 16 | u32::from(m.fetch());
+        match (insn >> 4) & 0xf {
+            0x0 => self.regs[dst] = self.read(Loc::Mem(self.regs[src]), Width::Addr),
+            0x1 => {
+                let addr = self.regs[src];
+                self.regs[src] = (addr + 4) & ADDR_MASK;
+                self.regs[dst] = self.read(Loc::Mem(addr), Width::Addr);
+            }
+            0x2 => {
+                let addr = imm20(self);
+                self.regs[dst] = self.read(Loc::Mem(addr), Width::Addr);
+            }
+            0x3 => {
+                let addr = self.index(self.regs[src], true);
+                self.regs[dst] = self.read(Loc::Mem(addr), Width::Addr);
+            }
+            0x6 => {
+                let addr = (dst as u32) << 16 | u32::from(self.fetch());
+                self.write(Loc::Mem(addr), self.regs[src], Width::Addr)?;
+            }
+            0x7 => {
+                let addr = self.index(self.regs[dst], true);
+                self.write(Loc::Mem(addr), self.regs[src], Width::Addr)?;
+            }
+            op @ 0x8..=0xf => {
+                let s = if op < 0xc {
+                    imm20(self)
+                } else {
+                    self.regs[src]
+                };
+                let d = self.regs[dst];
+                match op & 3 {
+                    0 => self.regs[dst] = s,
+                    1 => {
+                        self.add(d, !s, 1, Width::Addr);
+                    }
+                    2 => self.regs[dst] = self.add(d, s, 0, Width::Addr),
+                    _ => self.regs[dst] = self.add(d, !s, 1, Width::Addr),
+                }
+            }
+            _ => bail!("unsupported instruction {:#06x}", insn),
+        }
+        Ok(())
+    }
+
+    fn fetch(&mut self) -> u16 {
+        let word = self.word(self.regs[0]);
+        self.regs[0] = (self.regs[0] + 2) & ADDR_MASK;
+        word
+    }
+
+    // Indexed addresses wrap at 16 bits if the base is below 0x10000, and at 20 bits otherwise.
+    // The address instructions always use 20 bits
+    fn index(&mut self, base: u32, wide: bool) -> u32 {
+        let offset = self.fetch();
+        if base < 0x10000 && !wide {
+            (base + u32::from(offset)) & 0xffff
+        } else {
+            (base as i32 + i32::from(offset as i16)) as u32 & ADDR_MASK
+        }
+    }
+
+    // Resolves a source operand, including the constant generators
+    fn source(&mut self, reg: usize, mode: u16, width: Width) -> Loc {
+        match (mode, reg) {
+            (0, 3) => Loc::Const(0),
+            (0, _) => Loc::Reg(reg),
+            (1, 2) => Loc::Mem(u32::from(self.fetch())),
+            (1, 3) => Loc::Const(1),
+            (1, _) => {
+                // In symbolic mode the base is the address of the offset
+                let base = self.regs[reg];
+                Loc::Mem(self.index(base, false))
+            }
+            (2, 2) => Loc::Const(4),
+            (2, 3) => Loc::Const(2),
+            (2, _) => Loc::Mem(self.regs[reg]),
+            (_, 0) => Loc::Const(u32::from(self.fetch())),
+            (_, 2) => Loc::Const(8),
+            (_, 3) => Loc::Const(0xffff),
+            (_, _) => {
+                let addr = self.regs[reg];
+                let step = if width == Width::Byte && reg != 1 {
+                    1
+                } else {
+                    2
+                };
+                self.regs[reg] = (addr + step) & ADDR_MASK;
+                Loc::Mem(addr)
+            }
+        }
+    }
+
+    fn destination(&mut self, reg: usize, mode: u16) -> Loc {
+        match (mode, reg) {
+            (0, _) => Loc::Reg(reg),
+            (_, 2) => Loc::Mem(u32::from(self.fetch())),
+            (_, _) => {
+                let base = self.regs[reg];
+                Loc::Mem(self.index(base, false))
+            }
+        }
+    }
+
+    fn read(&self, loc: Loc, width: Width) -> u32 {
+        match loc {
+            Loc::Reg(r) => self.regs[r] & width.mask(),
+            Loc::Const(c) => c & width.mask(),
+            Loc::Mem(addr) => match width {
+                Width::Byte => u32::from(self.mem[(addr & ADDR_MASK) as usize]),
+                Width::Word => u32::from(self.word(addr)),
+                Width::Addr => {
+                    u32::from(self.word(addr)) | (u32::from(self.word(addr + 2)) & 0xf) << 16
+                }
+            },
+        }
+    }
+
+    fn write(&mut self, loc: Loc, value: u32, width: Width) -> Result<(), Error> {
+        let value = value & width.mask();
+        match loc {
+            // r3 is the constant generator; writes to it are discarded
+            Loc::Reg(3) => {}
+            // Byte and word operations clear the upper bits of a register
+            Loc::Reg(r) => self.regs[r] = value,
+            Loc::Const(_) => bail!("write to a constant"),
+            Loc::Mem(addr) => {
+                let addr = addr & ADDR_MASK;
+                self.events.push(Event::Write(addr));
+                match width {
+                    Width::Byte => self.mem[addr as usize] = value as u8,
+                    Width::Word => self.store(addr, value as u16),
+                    Width::Addr => {
+                        self.store(addr, value as u16);
+                        self.store(addr + 2, (value >> 16) as u16);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn store(&mut self, addr: u32, value: u16) {
+        let addr = (addr & ADDR_MASK & !1) as usize;
+        self.mem[addr..addr + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn push(&mut self, value: u32, width: Width) -> Result<(), Error> {
+        let size = if width == Width::Addr { 4 } else { 2 };
+        self.regs[1] = (self.regs[1].wrapping_sub(size)) & ADDR_MASK;
+        self.write(Loc::Mem(self.regs[1]), value, width)
+    }
+
+    fn pop(&mut self, width: Width) -> Result<u32, Error> {
+        let size = if width == Width::Addr { 4 } else { 2 };
+        let value = self.read(Loc::Mem(self.regs[1]), width);
+        self.regs[1] = (self.regs[1] + size) & ADDR_MASK;
+        Ok(value)
+    }
+
+    fn add(&mut self, d: u32, s: u32, carry: u32, width: Width) -> u32 {
+        let (d, s) = (d & width.mask(), s & width.mask());
+        let sum = d + s + carry;
+        let r = sum & width.mask();
+        let overflow = (d ^ r) & (s ^ r) & width.sign() != 0;
+        self.set_flags(r, sum > width.mask(), overflow, width);
+        r
+    }
+
+    fn logic_flags(&mut self, r: u32, overflow: bool, width: Width) {
+        self.set_flags(r, r != 0, overflow, width);
+    }
+
+    fn set_flags(&mut self, r: u32, carry: bool, overflow: bool, width: Width) {
+        let mut sr = self.regs[2] & !(C | Z | N | V);
+        if carry {
+            sr |= C;
+        }
+        if r & width.mask() == 0 {
+            sr |= Z;
+        }
+        if r & width.sign() != 0 {
+            sr |= N;
+        }
+        if overflow {
+            sr |= V;
+        }
+        self.regs[2] = sr;
+    }
+}
+
+fn align(addr: u32, align: u32) -> u32 {
+    addr.div_ceil(align) * align
+}
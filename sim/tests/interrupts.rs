@@ -0,0 +1,86 @@
+//! Runs the per-vector trampolines of the default interrupt handler from the blob
+
+use msp430_rt_sim::{Exit, Machine, Object, CLOBBERED};
+
+const SP: u32 = 0x03f0;
+const HANDLERS: u32 = 0xc000;
+const DISPATCH: u32 = 0xc100;
+const DISPATCH_X: u32 = 0xc200;
+const DEFAULT_HANDLER: u32 = 0xc300;
+const DEFAULT_HANDLER_IRQ: u32 = 0xc400;
+
+// The MSP430 and MSP430X variants of the blob
+const BLOBS: [&str; 2] = [
+    concat!(env!("CARGO_MANIFEST_DIR"), "/../bin/msp430-none-elf.a"),
+    concat!(env!("CARGO_MANIFEST_DIR"), "/../bin/msp430x-none-elf.a"),
+];
+
+// Links the trampolines and the dispatch with `DefaultHandler` and, if non-zero,
+// `DefaultHandlerIrq`, which is weak. `x` selects the dispatch that saves 20-bit registers, as
+// upper.x does. DefaultHandlerIrq is Rust code either way, which only saves the lower 16 bits of r4
+// to r10
+fn link(m: &mut Machine, blob: &str, default_handler_irq: u32, x: bool) {
+    let object = Object::from_file(blob).unwrap();
+    m.stub("DefaultHandler", DEFAULT_HANDLER);
+    if default_handler_irq != 0 {
+        m.stub_16("DefaultHandlerIrq", default_handler_irq);
+    }
+    m.symbol("__DEFAULT_DISPATCH", if x { DISPATCH_X } else { DISPATCH });
+    m.link(&object, HANDLERS, &[".text.__DefaultHandlers"])
+        .unwrap();
+    m.link(&object, DISPATCH, &[".text.__DefaultDispatch"])
+        .unwrap();
+    m.link(&object, DISPATCH_X, &[".text.__DefaultDispatchX"])
+        .unwrap();
+}
+
+// The state right after the CPU took an interrupt: SR and PC on the stack, registers in use. With
+// `x`, the registers hold 20-bit values
+fn machine(blob: &str, default_handler_irq: u32, x: bool) -> Machine {
+    let mut m = Machine::new();
+    m.load(SP, &[0x08, 0x00, 0x34, 0xc0]);
+    m.regs[1] = SP;
+    for (r, reg) in m.regs.iter_mut().enumerate().skip(4) {
+        *reg = 0x1100 * r as u32 + if x { 0x10000 * (r as u32 - 3) } else { 0 };
+    }
+    link(&mut m, blob, default_handler_irq, x);
+    m
+}
+
+#[test]
+fn irq_handler_gets_vector_index() {
+    for (blob, x) in BLOBS.iter().flat_map(|blob| [(blob, false), (blob, true)]) {
+        for irq in [0, 1, 5, 14, 62] {
+            let mut m = machine(blob, DEFAULT_HANDLER_IRQ, x);
+            let regs = m.regs;
+
+            let exit = m.run(&format!("__DefaultHandler{}", irq));
+            assert_eq!(exit, Ok(Exit::Reti));
+            assert_eq!(m.calls(), ["DefaultHandlerIrq"]);
+            assert_eq!(m.call_args, [irq]);
+            // `reti` popped what the interrupt pushed, and the registers that DefaultHandlerIrq may
+            // clobber or truncate are back, all 20 bits of them
+            assert_eq!(m.regs[0], 0xc034);
+            assert_eq!(m.regs[1], SP + 4);
+            assert_eq!(m.regs[2], 0x0008);
+            assert_eq!(m.regs[4..], regs[4..]);
+            assert!(!m.regs.contains(&CLOBBERED));
+        }
+    }
+}
+
+#[test]
+fn falls_back_to_default_handler() {
+    for (blob, x) in BLOBS.iter().flat_map(|blob| [(blob, false), (blob, true)]) {
+        let mut m = machine(blob, 0, x);
+        let regs = m.regs;
+
+        let exit = m.run("__DefaultHandler3");
+        assert_eq!(exit, Ok(Exit::Branch("DefaultHandler".into())));
+        assert!(m.calls().is_empty());
+        // DefaultHandler sees the stack and registers the interrupt left. Only the flags in SR
+        // differ, and `reti` restores those
+        assert_eq!(m.regs[1], regs[1]);
+        assert_eq!(m.regs[4..], regs[4..]);
+    }
+}
@@ -0,0 +1,701 @@
+//! Links small programs with link.x, as build.rs writes it, using the host's GNU assembler and
+//! linker (`as --32` and `ld -m elf_i386`). The programs have the same sections, symbols and
+//! references as the blob and the msp430-rt crate, just not MSP430 code, so what ld makes of the
+//! linker scripts is the same as for a real application.
+
+use std::{
+    env,
+    fmt::Write as _,
+    fs,
+    path::Path,
+    process::Command,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use msp430_rt_sim::Object;
+
+#[allow(dead_code)]
+mod build {
+    include!("../../build.rs");
+
+    pub fn run() {
+        main()
+    }
+}
+
+const BLOB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../bin/msp430-none-elf.a");
+
+// The memory.x example in the msp430-rt documentation (MSP430G2553)
+const MEMORY_X: &str = "
+MEMORY
+{
+  RAM : ORIGIN = 0x0200, LENGTH = 0x0200
+  ROM : ORIGIN = 0xC000, LENGTH = 0x3FE0
+  VECTORS : ORIGIN = 0xFFE0, LENGTH = 0x20
+}
+";
+
+// An MSP430FR5994 with every region that a feature needs
+const FR_MEMORY_X: &str = "
+MEMORY
+{
+  RAM : ORIGIN = 0x1C00, LENGTH = 0x0800
+  INFOD : ORIGIN = 0x1800, LENGTH = 0x80
+  INFOC : ORIGIN = 0x1880, LENGTH = 0x80
+  INFOB : ORIGIN = 0x1900, LENGTH = 0x80
+  INFOA : ORIGIN = 0x1980, LENGTH = 0x80
+  RAM2 : ORIGIN = 0x2C00, LENGTH = 0x1000
+  IPE : ORIGIN = 0x4400, LENGTH = 0x0C00
+  ROM : ORIGIN = 0x5000, LENGTH = 0xAF80
+  JTAGSIGNATURE : ORIGIN = 0xFF80, LENGTH = 0x4
+  BSLSIGNATURE : ORIGIN = 0xFF84, LENGTH = 0x4
+  IPESIGNATURE : ORIGIN = 0xFF88, LENGTH = 0x4
+  VECTORS : ORIGIN = 0xFF90, LENGTH = 0x70
+  HIROM : ORIGIN = 0x10000, LENGTH = 0x30000
+  HIRAM : ORIGIN = 0x40000, LENGTH = 0x4000
+}
+
+__WDTCTL_ADDR = 0x015C;
+__SYSRSTIV_ADDR = 0x019E;
+_stack_size = 0x100;
+";
+
+// Every feature but `device`, which needs a pac crate
+const FEATURES: &[&str] = &[
+    "boot-info",
+    "fram-mpu",
+    "hold-watchdog",
+    "info-mem",
+    "ipe",
+    "jtag-signature",
+    "lpm5-wakeup",
+    "paint-stack",
+    "ram2",
+    "retain-ram",
+    "scrub-ram",
+    "upper-memory",
+];
+
+// build.rs takes its input from the environment, which all the tests share
+static BUILD: Mutex<()> = Mutex::new(());
+static LINKS: AtomicUsize = AtomicUsize::new(0);
+
+// Runs build.rs for a generic application with `features`, and the length of the VECTORS region
+// in `memory_x`
+fn build(out: &Path, features: &[&str], vectors_length: &str) {
+    let _lock = BUILD.lock().unwrap_or_else(|e| e.into_inner());
+    for (var, _) in env::vars_os() {
+        if var.to_string_lossy().starts_with("CARGO_FEATURE_") {
+            env::remove_var(var);
+        }
+    }
+    for feature in features {
+        let var = format!("CARGO_FEATURE_{}", feature.to_uppercase().replace('-', "_"));
+        env::set_var(var, "1");
+    }
+    env::set_var("TARGET", "msp430-rt-sim-link-test");
+    env::set_var("OUT_DIR", out);
+    env::set_var("PROFILE", "debug");
+    env::set_var("MSP430_RT_VECTORS_LENGTH", vectors_length);
+    build::run();
+}
+
+// Assembly for the sections of the blob, with the same sizes, global symbols and references
+fn blob() -> String {
+    let object = Object::from_file(BLOB).unwrap();
+    let mut s = String::new();
+    for section in object.sections().iter().filter(|s| s.alloc) {
+        let nobits = section.data.is_empty();
+        writeln!(
+            s,
+            "  .section {}, \"{}\", @{}\n  .balign {}",
+            section.name,
+            if nobits { "aw" } else { "ax" },
+            if nobits { "nobits" } else { "progbits" },
+            section.align
+        )
+        .unwrap();
+
+        // Labels and references, in the order of their offsets
+        let mut lines = object
+            .globals(&section.name)
+            .into_iter()
+            .map(|(name, offset)| (offset, 0, format!("  .global {0}\n{0}:", name)))
+            .chain(object.relocations(&section.name).into_iter().map(|r| {
+                let directive = if r.size == 4 { ".long" } else { ".short" };
+                let weak = if r.weak { "  .weak " } else { "  # " };
+                let line = format!(
+                    "{}{}\n  {} {}{:+}",
+                    weak, r.symbol, directive, r.symbol, r.addend
+                );
+                (r.offset, r.size, line)
+            }))
+            .collect::<Vec<_>>();
+        lines.sort_by_key(|&(offset, size, _)| (offset, size));
+        let mut at = 0;
+        for (offset, size, line) in lines {
+            writeln!(s, "  .skip {}\n{}", offset - at, line).unwrap();
+            at = offset + size;
+        }
+        writeln!(s, "  .skip {}", section.size - at).unwrap();
+    }
+    s
+}
+
+// Assembly for what the msp430-rt crate and a minimal application put in the program
+fn crate_sections(interrupts: usize) -> String {
+    let mut s = String::from(
+        "
+  .section .__RESET_VECTOR, \"a\"
+  .global __RESET_VECTOR
+__RESET_VECTOR:
+  .short Reset
+  .section .text.PreInit_, \"ax\"
+  .global PreInit_
+PreInit_:
+  .skip 2
+  .section .text.DefaultHandler_, \"ax\"
+  .global DefaultHandler_
+DefaultHandler_:
+  .skip 2
+  .section .text.main, \"ax\"
+  .global main
+main:
+  .skip 2
+  .section .vector_table.interrupts, \"a\"
+  .global __INTERRUPTS
+__INTERRUPTS:
+",
+    );
+    for n in 0..interrupts {
+        writeln!(s, "  .short __Vector{}", n).unwrap();
+    }
+    s
+}
+
+// Links a generic application made of the blob, the msp430-rt crate and `program` (assembly), with
+// `features`. Returns the linked program, or what ld printed if the link failed
+fn link(features: &[&str], memory_x: &str, program: &str) -> Result<Object, String> {
+    let vectors_length = memory_x
+        .lines()
+        .find(|l| l.trim_start().starts_with("VECTORS"))
+        .and_then(|l| l.split("LENGTH = ").nth(1))
+        .unwrap();
+    link_with_vectors(features, memory_x, program, vectors_length.trim())
+}
+
+// Same as `link`, with `MSP430_RT_VECTORS_LENGTH` set to `vectors_length`
+fn link_with_vectors(
+    features: &[&str],
+    memory_x: &str,
+    program: &str,
+    vectors_length: &str,
+) -> Result<Object, String> {
+    let out = env::temp_dir().join(format!(
+        "msp430-rt-link-{}-{}",
+        std::process::id(),
+        LINKS.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::create_dir_all(&out).unwrap();
+
+    build(&out, features, vectors_length);
+    fs::write(out.join("memory.x"), memory_x).unwrap();
+
+    // With the `device` feature, `program` provides the vectors of __INTERRUPTS and device.x is
+    // empty
+    let interrupts = match fs::read_to_string(out.join("interrupts.rs")) {
+        Ok(interrupts) => interrupts
+            .lines()
+            .next()
+            .and_then(|l| l.strip_prefix("const INTERRUPTS: usize = "))
+            .and_then(|n| n.trim_end_matches(';').parse().ok())
+            .unwrap(),
+        Err(_) => {
+            fs::write(out.join("device.x"), "").unwrap();
+            0
+        }
+    };
+    let source = out.join("program.s");
+    fs::write(&source, blob() + &crate_sections(interrupts) + program).unwrap();
+
+    let object = out.join("program.o");
+    run(Command::new("as")
+        .arg("--32")
+        .arg(&source)
+        .arg("-o")
+        .arg(&object))?;
+    let elf = out.join("program.elf");
+    // rustc passes the objects before the `-C link-arg`s, and ld gives the definitions in an
+    // object precedence over the `PROVIDE`s in a script that only comes after it
+    let result = run(Command::new("ld")
+        .args(["-m", "elf_i386", "--gc-sections", "-nostdlib"])
+        .arg(&object)
+        .arg("-L")
+        .arg(&out)
+        .args(["-T", "link.x", "-o"])
+        .arg(&elf))
+    .map(|_| Object::from_file(&elf).unwrap());
+
+    fs::remove_dir_all(&out).unwrap();
+    result
+}
+
+fn run(command: &mut Command) -> Result<(), String> {
+    let output = command.output().unwrap_or_else(|e| {
+        panic!(
+            "couldn't run {:?} ({}); these tests need the host's GNU assembler and linker",
+            command, e
+        )
+    });
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).into_owned())
+    }
+}
+
+fn symbol(object: &Object, name: &str) -> u32 {
+    object
+        .symbol(name)
+        .unwrap_or_else(|| panic!("`{}` is not defined", name))
+}
+
+fn words(object: &Object, addr: u32, n: u32) -> Vec<u16> {
+    (0..n).map(|i| object.word(addr + 2 * i).unwrap()).collect()
+}
+
+fn expect_error(result: Result<Object, String>, message: &str) {
+    match result {
+        Ok(_) => panic!("the link succeeded, expected `{}`", message),
+        Err(e) => assert!(e.contains(message), "expected `{}` in:\n{}", message, e),
+    }
+}
+
+// What the `#[lpm5_wakeup]` attribute defines
+const LPM5_WAKEUP: &str = "
+  .section .text.wakeup, \"ax\"
+  .global Lpm5Wakeup
+Lpm5Wakeup:
+  ret
+";
+
+#[test]
+fn every_feature_links() {
+    link(&[], MEMORY_X, "").unwrap();
+    for &feature in FEATURES {
+        if let Err(e) = link(&[feature], FR_MEMORY_X, LPM5_WAKEUP) {
+            panic!("`{}`:\n{}", feature, e);
+        }
+    }
+    link(FEATURES, FR_MEMORY_X, LPM5_WAKEUP).unwrap();
+}
+
+#[test]
+fn reset_parts() {
+    let blob = Object::from_file(BLOB).unwrap();
+    let size = |sections: &[&str]| {
+        sections
+            .iter()
+            .map(|s| blob.section(s).unwrap().size)
+            .sum::<u32>()
+    };
+    // Nothing comes between Reset and DefaultHandler_ without a DefaultHandlerIrq
+    let reset = |o: &Object| symbol(o, "DefaultHandler_") - symbol(o, "Reset");
+    let uninit = |o: &Object| symbol(o, "_euninit") - symbol(o, "_suninit");
+    let common = size(&[
+        ".Reset",
+        ".Reset.start",
+        ".Reset.pre_init",
+        ".Reset.init_ram",
+        ".Reset.main",
+    ]);
+
+    // Without features, Reset only has the parts that every program needs, doesn't read the reset
+    // cause and keeps nothing in .uninit
+    for memory_x in [MEMORY_X, FR_MEMORY_X] {
+        let o = link(&[], memory_x, "").unwrap();
+        assert_eq!(reset(&o), common);
+        assert_eq!(o.symbol("__SYSRSTIV"), None);
+        assert_eq!(o.symbol("__NoResetCause"), None);
+        assert_eq!(uninit(&o), 0);
+    }
+
+    // With `boot-info`, it reads a reset cause of 0 without SYSRSTIV
+    let o = link(&["boot-info"], MEMORY_X, "").unwrap();
+    assert_eq!(symbol(&o, "__SYSRSTIV"), symbol(&o, "__NoResetCause"));
+    let o = link(&["boot-info"], FR_MEMORY_X, "").unwrap();
+    assert_eq!(symbol(&o, "__SYSRSTIV"), 0x019e);
+    assert_eq!(o.symbol("__NoResetCause"), None);
+
+    // Each feature adds its own parts, and only the .uninit bytes it uses. `lpm5-wakeup` and
+    // `retain-ram` enable `boot-info`, as Cargo.toml does, and `scrub-ram` with `retain-ram` adds
+    // a part of its own
+    let boot_info = &[".Reset.boot_info_entry", ".Reset.boot_info"][..];
+    let retain_ram = &[".Reset.retain_check", ".Reset.retain_store"][..];
+    for (features, parts, uninit_size) in [
+        (&["boot-info"][..], vec![boot_info], 14),
+        (&["fram-mpu"][..], vec![&[".Reset.mpu"][..]], 0),
+        (
+            &["lpm5-wakeup", "boot-info"][..],
+            vec![boot_info, &[".Reset.lpm5_wakeup"][..]],
+            14,
+        ),
+        (&["paint-stack"][..], vec![&[".Reset.paint_stack"][..]], 0),
+        (
+            &["retain-ram", "boot-info"][..],
+            vec![boot_info, retain_ram],
+            18,
+        ),
+        (
+            &["scrub-ram"][..],
+            vec![&[".Reset.scrub_ram", ".Reset.scrub_regs"][..]],
+            0,
+        ),
+        (
+            &["scrub-ram", "retain-ram", "boot-info"][..],
+            vec![
+                boot_info,
+                retain_ram,
+                &[
+                    ".Reset.scrub_check",
+                    ".Reset.scrub_ram",
+                    ".Reset.scrub_regs",
+                ][..],
+            ],
+            18,
+        ),
+        (&["upper-memory"][..], vec![&[".Reset.upper_init"][..]], 0),
+    ] {
+        let o = link(features, FR_MEMORY_X, LPM5_WAKEUP).unwrap();
+        let added = parts.iter().map(|p| size(p)).sum::<u32>();
+        assert_eq!(reset(&o), common + added, "{:?}", features);
+        assert_eq!(uninit(&o), uninit_size, "{:?}", features);
+    }
+}
+
+#[test]
+fn init_tables() {
+    let program = "
+  .section .bss.x, \"aw\", @nobits
+x:
+  .skip 0x12
+  .section .data.y, \"aw\"
+y:
+  .fill 6, 1, 0x11
+  .section .ramtext.f, \"ax\"
+f:
+  .fill 4, 1, 0x22
+  .section .text.main, \"ax\"
+  .long x, y, f
+";
+    let o = link(&[], MEMORY_X, program).unwrap();
+
+    let (sbss, sdata, sramtext) = (0x0200, 0x0212, 0x0218);
+    assert_eq!(symbol(&o, "_sbss"), sbss);
+    assert_eq!(symbol(&o, "_sdata"), sdata);
+    assert_eq!(symbol(&o, "_sramtext"), sramtext);
+    assert_eq!(words(&o, sdata, 3), [0x1111; 3]);
+    assert_eq!(words(&o, sramtext, 2), [0x2222; 2]);
+    // The load addresses are in ROM, one after the other
+    let (sidata, siramtext) = (symbol(&o, "_sidata"), symbol(&o, "_siramtext"));
+    assert!(sidata >= 0xc000 && sidata % 2 == 0);
+    assert_eq!(siramtext, sidata + 6);
+
+    // .bss, then .data and .ramtext. The tables only have entries for .bss2 and .data2 with the
+    // `ram2` feature, and the upper tables are empty without `upper-memory`
+    let zero = symbol(&o, "__zero_table_start");
+    assert_eq!(symbol(&o, "__zero_table_end"), zero + 4);
+    assert_eq!(words(&o, zero, 2), [sbss as u16, 0x12]);
+    let copy = symbol(&o, "__copy_table_start");
+    assert_eq!(symbol(&o, "__copy_table_end"), copy + 12);
+    assert_eq!(
+        words(&o, copy, 6),
+        [
+            sidata as u16,
+            sdata as u16,
+            6,
+            siramtext as u16,
+            sramtext as u16,
+            4
+        ]
+    );
+    let upper = symbol(&o, "__upper_zero_table_start");
+    assert_eq!(symbol(&o, "__upper_copy_table_end"), upper);
+    // The retain tables are empty without `retain-ram`
+    let retain = symbol(&o, "__retain_table_start");
+    assert_eq!(symbol(&o, "__upper_retain_table_end"), retain);
+
+    let o = link(&["ram2", "upper-memory"], FR_MEMORY_X, program).unwrap();
+    let zero = symbol(&o, "__zero_table_start");
+    assert_eq!(symbol(&o, "__zero_table_end"), zero + 8);
+    assert_eq!(words(&o, zero + 4, 2), [0x2c00, 0]);
+    let copy = symbol(&o, "__copy_table_start");
+    assert_eq!(symbol(&o, "__copy_table_end"), copy + 18);
+    assert_eq!(words(&o, copy + 14, 2), [0x2c00, 0]);
+    let upper = symbol(&o, "__upper_zero_table_start");
+    assert_eq!(symbol(&o, "__upper_zero_table_end"), upper + 8);
+    assert_eq!(symbol(&o, "__upper_copy_table_start"), upper + 8);
+    assert_eq!(symbol(&o, "__upper_copy_table_end"), upper + 20);
+    assert_eq!(words(&o, upper, 4), [0, 4, 0, 0]);
+
+    // With `retain-ram`, the retain tables list the sections of the other tables, but .ramtext
+    let features = &["retain-ram", "boot-info", "ram2", "upper-memory"];
+    let o = link(features, FR_MEMORY_X, program).unwrap();
+    let (sbss, sdata) = (symbol(&o, "_sbss"), symbol(&o, "_sdata"));
+    let retain = symbol(&o, "__retain_table_start");
+    assert_eq!(symbol(&o, "__retain_table_end"), retain + 16);
+    assert_eq!(
+        words(&o, retain, 8),
+        [sbss as u16, 0x12, sdata as u16, 6, 0x2c00, 0, 0x2c00, 0]
+    );
+    let upper = symbol(&o, "__upper_retain_table_start");
+    assert_eq!(symbol(&o, "__upper_retain_table_end"), upper + 16);
+    assert_eq!(words(&o, upper, 8), [0, 4, 0, 0, 0, 4, 0, 0]);
+}
+
+#[test]
+fn lpm5_wakeup_needs_handler_and_sysrstiv() {
+    let features = &["lpm5-wakeup", "boot-info"];
+    link(features, FR_MEMORY_X, LPM5_WAKEUP).unwrap();
+    expect_error(
+        link(features, FR_MEMORY_X, ""),
+        "ERROR(msp430-rt): The 'lpm5-wakeup' feature is enabled, but the program has no \
+         #[lpm5_wakeup]",
+    );
+    let memory_x = FR_MEMORY_X.replace("__SYSRSTIV_ADDR = 0x019E;", "");
+    expect_error(
+        link(features, &memory_x, LPM5_WAKEUP),
+        "ERROR(msp430-rt): The 'lpm5-wakeup' feature is enabled, but __SYSRSTIV_ADDR is not set",
+    );
+}
+
+#[test]
+fn stack_reservation() {
+    let memory_x = format!("{}\n_stack_size = 0x100;", MEMORY_X);
+    let bss = |size: u32| {
+        format!(
+            concat!(
+                "  .section .bss.x, \"aw\", @nobits\nx:\n  .skip {}\n",
+                "  .section .text.main, \"ax\"\n  .long x\n",
+            ),
+            size
+        )
+    };
+
+    // The RAM left for the stack is exactly _stack_size, and then some bytes short of it
+    let o = link(&[], &memory_x, &bss(0x10)).unwrap();
+    let free = 0x0400 - 0x100 - symbol(&o, "_euninit");
+    let o = link(&[], &memory_x, &bss(0x10 + free)).unwrap();
+    assert_eq!(symbol(&o, "_euninit"), 0x0300);
+    assert_eq!(symbol(&o, "_eheap"), 0x0300);
+    for missing in [2, 6, 0x20] {
+        expect_error(
+            link(&[], &memory_x, &bss(0x10 + free + missing)),
+            &format!("region `RAM' overflowed by {} bytes", missing),
+        );
+    }
+    expect_error(
+        link(&[], &memory_x, &bss(0x12 + free)),
+        "RAM usage leaves less than _stack_size bytes for the stack",
+    );
+}
+
+#[test]
+fn paint_stack_reservation() {
+    let o = link(&["paint-stack"], FR_MEMORY_X, "").unwrap();
+    assert_eq!(symbol(&o, "_spaint"), 0x2400 - 0x100);
+    assert_eq!(symbol(&o, "_stack_start"), 0x2400);
+
+    let memory_x = FR_MEMORY_X.replace("_stack_size = 0x100;", "");
+    expect_error(
+        link(&["paint-stack"], &memory_x, ""),
+        "ERROR(msp430-rt): The 'paint-stack' feature is enabled, but _stack_size is not set",
+    );
+}
+
+#[test]
+fn mpu_segment_borders() {
+    let program = "
+  .section .persistent.p, \"aw\"
+p:
+  .fill 6, 1, 7
+  .section .text.main, \"ax\"
+  .long p
+";
+    let o = link(&["fram-mpu"], FR_MEMORY_X, program).unwrap();
+
+    let (start, end) = (symbol(&o, "_spersistent"), symbol(&o, "_epersistent"));
+    assert_eq!(start % 1024, 0);
+    assert_eq!(end, start + 1024);
+    assert_eq!(symbol(&o, "__MPUSEGB1"), start >> 4);
+    assert_eq!(symbol(&o, "__MPUSEGB2"), end >> 4);
+    assert_eq!(symbol(&o, "__MPUCTL0_ADDR"), 0x05a0);
+    assert_eq!(symbol(&o, "__MPUSAM"), 0x3535);
+}
+
+#[test]
+fn upper_data_writable_with_mpu() {
+    let program = "
+  .section .upper.bss.u, \"aw\", @nobits
+u:
+  .skip 4
+  .section .text.main, \"ax\"
+  .long u
+";
+    let features = ["fram-mpu", "upper-memory"];
+
+    // HIRAM is above .persistent, in segment 3
+    expect_error(
+        link(&features, FR_MEMORY_X, program),
+        "ERROR(msp430-rt): The MPU makes the FRAM above .persistent read-only",
+    );
+
+    let memory_x = format!("{}\n__MPUSAM = 0x3735;", FR_MEMORY_X);
+    link(&features, &memory_x, program).unwrap();
+
+    let memory_x = FR_MEMORY_X.replace("  HIRAM : ORIGIN = 0x40000, LENGTH = 0x4000\n", "")
+        + "\nREGION_ALIAS(\"HIRAM\", RAM);";
+    let o = link(&features, &memory_x, program).unwrap();
+    assert!(symbol(&o, "u") < symbol(&o, "_spersistent"));
+}
+
+#[test]
+fn ipe_init_structure() {
+    let program = "
+  .section .ipe.secret, \"ax\"
+secret:
+  .fill 10, 1, 9
+  .section .text.main, \"ax\"
+  .long secret
+";
+    let o = link(&["ipe"], FR_MEMORY_X, program).unwrap();
+
+    let (start, end) = (symbol(&o, "_sipe"), symbol(&o, "_eipe"));
+    assert_eq!((start, end), (0x4400, 0x4800));
+    let s = words(&o, start, 4);
+    assert_eq!(s[..3], [0x00c0, (end >> 4) as u16, (start >> 4) as u16]);
+    assert_eq!(s[3], !(s[0] ^ s[1] ^ s[2]));
+    assert_eq!(words(&o, 0xff88, 2), [0xaaaa, (start >> 4) as u16]);
+}
+
+#[test]
+fn unused_vectors() {
+    let vectors = |o: &Object| words(o, symbol(o, "_sinterrupts"), 15);
+
+    // Each vector points to DefaultHandler, and there are no trampolines
+    let o = link(&[], MEMORY_X, "").unwrap();
+    assert_eq!(vectors(&o), [symbol(&o, "DefaultHandler_") as u16; 15]);
+    assert_eq!(o.symbol("__DefaultHandler0"), None);
+    assert_eq!(o.symbol("__DefaultDispatch"), None);
+
+    // Or to its trampoline, with a DefaultHandlerIrq
+    let default_handler_irq = "
+  .section .text.DefaultHandlerIrq, \"ax\"
+  .global DefaultHandlerIrq
+DefaultHandlerIrq:
+  .skip 2
+";
+    let o = link(&[], MEMORY_X, default_handler_irq).unwrap();
+    let trampolines = (0..15)
+        .map(|n| symbol(&o, &format!("__DefaultHandler{}", n)) as u16)
+        .collect::<Vec<_>>();
+    assert_eq!(vectors(&o), trampolines);
+    assert!(o.symbol("__DefaultDispatch").is_some());
+    assert_eq!(o.symbol("__DefaultDispatchX"), None);
+
+    // The trampolines are one block, so the code after them is where it is with fewer vectors
+    let default_handler = symbol(&o, "DefaultHandler_");
+    let memory_x = MEMORY_X.replace("0xFFE0, LENGTH = 0x20", "0xFFF0, LENGTH = 0x10");
+    let o = link(&[], &memory_x, default_handler_irq).unwrap();
+    assert_eq!(symbol(&o, "DefaultHandler_"), default_handler);
+
+    // The trampolines call the dispatch that saves 20-bit registers with `upper-memory`
+    let o = link(&["upper-memory"], FR_MEMORY_X, default_handler_irq).unwrap();
+    assert_eq!(o.symbol("__DefaultDispatch"), None);
+    assert!(o.symbol("__DefaultDispatchX").is_some());
+
+    // Or all hold __VECTOR_FILL, which leaves no room for a DefaultHandler, and no trampolines
+    let memory_x = format!("{}\n__VECTOR_FILL = 0xFFFF;", MEMORY_X);
+    let o = link(&[], &memory_x, "").unwrap();
+    assert_eq!(vectors(&o), [0xffff; 15]);
+    let default_handler = "
+  .section .text.DefaultHandler, \"ax\"
+  .global DefaultHandler
+DefaultHandler:
+  .skip 2
+";
+    expect_error(
+        link(&[], &memory_x, default_handler),
+        "__VECTOR_FILL is set, so the unused interrupt vectors don't point to the",
+    );
+}
+
+#[test]
+fn handlers_below_0x10000() {
+    let default_handler = "
+  .section .upper.text.DefaultHandler, \"ax\"
+  .global DefaultHandler
+DefaultHandler:
+  .skip 2
+";
+    expect_error(
+        link(&["upper-memory"], FR_MEMORY_X, default_handler),
+        "is placed above 0x10000, but the",
+    );
+}
+
+#[test]
+fn vector_count_mismatch() {
+    // LENGTH(VECTORS) is 0x70, which fits 55 vectors
+    for message in [
+        "msp430-rt generated 15 interrupt vectors (__INTERRUPTS) for a VECTORS\nregion of 0x20 bytes",
+        "region `VECTORS' overflowed by 80 bytes",
+    ] {
+        expect_error(link_with_vectors(&[], FR_MEMORY_X, "", "0x20"), message);
+    }
+    for message in [
+        "msp430-rt generated 63 interrupt vectors (__INTERRUPTS) for a VECTORS\nregion of 0x80 bytes",
+        "region `VECTORS' overflowed by 16 bytes",
+    ] {
+        expect_error(link_with_vectors(&[], FR_MEMORY_X, "", "0x80"), message);
+    }
+
+    // A pac crate with one vector too few, and one too many
+    let vectors = |n: usize| {
+        "  .section .vector_table.interrupts, \"a\"\n".to_string() + &"  .short 0\n".repeat(n)
+    };
+    link(&["device"], FR_MEMORY_X, &vectors(55)).unwrap();
+    for (n, overflow) in [(54, 2), (56, 2)] {
+        for message in [
+            "The number of interrupt vectors in __INTERRUPTS doesn't match the length",
+            &format!("region `VECTORS' overflowed by {} bytes", overflow),
+        ] {
+            expect_error(link(&["device"], FR_MEMORY_X, &vectors(n)), message);
+        }
+    }
+}
+
+#[test]
+fn jtag_signature_in_debug_builds() {
+    for signature in [
+        "__JTAG_SIGNATURE_1 = 0x5555;",
+        "__JTAG_SIGNATURE_1 = 0xAAAA;",
+        "__JTAG_SIGNATURE_2 = 0x5555;",
+    ] {
+        let memory_x = format!("{}\n{}", FR_MEMORY_X, signature);
+        expect_error(
+            link(&["jtag-signature"], &memory_x, ""),
+            "ERROR(msp430-rt): The JTAG signature can lock JTAG access",
+        );
+    }
+
+    let memory_x = format!(
+        "{}\n__JTAG_SIGNATURE_1 = 0;\n__JTAG_SIGNATURE_2 = 0;",
+        FR_MEMORY_X
+    );
+    let o = link(&["jtag-signature"], &memory_x, "").unwrap();
+    assert_eq!(words(&o, 0xff80, 4), [0, 0, 0xffff, 0xffff]);
+}
@@ -0,0 +1,789 @@
+//! Runs `Reset` from the blob and compares the resulting memory with a byte-wise initialization of
+//! `.bss`, `.data` and `.ramtext`.
+
+use msp430_rt_sim::{Event, Exit, Machine, Object};
+
+const RAM: (u32, u32) = (0x0200, 0x0400);
+const SIDATA: u32 = 0xc100;
+const SIRAMTEXT: u32 = 0xc200;
+const TABLES: u32 = 0xc000;
+const RESET: u32 = 0xc800;
+const UPPER_INIT: u32 = 0xc500;
+const LPM5_WAKEUP: u32 = 0xc600;
+const NO_RESET_CAUSE: u32 = 0xc780;
+// Outside of the RAM checked against the reference, like the rest of .uninit
+const BOOT_INFO: u32 = 0x0100;
+const RETAIN_AREA: u32 = 0x0110;
+
+struct Layout {
+    sbss: u32,
+    ebss: u32,
+    sdata: u32,
+    edata: u32,
+    sramtext: u32,
+    eramtext: u32,
+}
+
+fn object() -> Object {
+    Object::from_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../bin/msp430-none-elf.a"
+    ))
+    .unwrap()
+}
+
+#[allow(dead_code)]
+mod build {
+    include!("../../build.rs");
+
+    // The sections of `Reset`, in the order reset.x places them, and the features each one needs
+    pub const PARTS: &[(&str, &[&str])] = RESET_PARTS;
+}
+
+// Links `Reset` without features with the symbols defined so far and runs it
+fn reset(m: &mut Machine) -> Result<Exit, msp430_rt_sim::Error> {
+    reset_with(m, &[])
+}
+
+// Same as `reset`, with the parts of `features`
+fn reset_with(m: &mut Machine, features: &[&str]) -> Result<Exit, msp430_rt_sim::Error> {
+    let parts = build::PARTS
+        .iter()
+        .filter(|(_, needs)| needs.iter().all(|f| features.contains(f)))
+        .map(|&(section, _)| section)
+        .collect::<Vec<_>>();
+    m.link(&object(), RESET, &parts)?;
+    m.run("Reset")
+}
+
+// Writes the zero and copy tables that link.x generates, and the symbols that bound them
+fn tables(m: &mut Machine, zero: &[(u32, u32)], copy: &[(u32, u32, u32)]) {
+    let zero_words = zero.iter().flat_map(|&(dst, len)| [dst, len]);
+    let copy_words = copy.iter().flat_map(|&(src, dst, len)| [src, dst, len]);
+    let bytes = zero_words
+        .chain(copy_words)
+        .flat_map(|word| (word as u16).to_le_bytes())
+        .collect::<Vec<_>>();
+    m.load(TABLES, &bytes);
+
+    let copy_start = TABLES + 4 * zero.len() as u32;
+    m.symbol("__zero_table_start", TABLES)
+        .symbol("__zero_table_end", copy_start)
+        .symbol("__copy_table_start", copy_start)
+        .symbol("__copy_table_end", TABLES + bytes.len() as u32);
+}
+
+// Fills RAM with garbage and `.data`'s load address with its initial values
+fn machine(layout: &Layout) -> Machine {
+    let mut m = Machine::new();
+    let garbage = (RAM.0..RAM.1).map(|a| (a as u8).wrapping_mul(7) | 1);
+    m.load(RAM.0, &garbage.collect::<Vec<_>>());
+    let init = (0..layout.edata - layout.sdata).map(|i| 0xa0 ^ i as u8);
+    m.load(SIDATA, &init.collect::<Vec<_>>());
+    let code = (0..layout.eramtext - layout.sramtext).map(|i| 0x50 ^ i as u8);
+    m.load(SIRAMTEXT, &code.collect::<Vec<_>>());
+    tables(
+        &mut m,
+        &[(layout.sbss, layout.ebss - layout.sbss)],
+        &[
+            (SIDATA, layout.sdata, layout.edata - layout.sdata),
+            (
+                SIRAMTEXT,
+                layout.sramtext,
+                layout.eramtext - layout.sramtext,
+            ),
+        ],
+    );
+
+    m.stub("PreInit", 0xc700)
+        .stub("main", 0xc702)
+        .symbol("_stack_start", RAM.1)
+        .symbol("__WDTCTL_ADDR", 0)
+        .symbol("__BOOT_INFO", BOOT_INFO)
+        .symbol("__SYSRSTIV", NO_RESET_CAUSE);
+    m.link(&object(), NO_RESET_CAUSE, &[".rodata.__NoResetCause"])
+        .unwrap();
+    m
+}
+
+// What the byte-at-a-time `Reset` used to do
+fn reference(layout: &Layout, mem: &[u8]) -> Vec<u8> {
+    let mut mem = mem.to_vec();
+    for a in layout.sbss..layout.ebss {
+        mem[a as usize] = 0;
+    }
+    for i in 0..layout.edata - layout.sdata {
+        mem[(layout.sdata + i) as usize] = mem[(SIDATA + i) as usize];
+    }
+    for i in 0..layout.eramtext - layout.sramtext {
+        mem[(layout.sramtext + i) as usize] = mem[(SIRAMTEXT + i) as usize];
+    }
+    mem
+}
+
+// Memory without the linked code and the boot info, which is checked by its own tests
+fn without_boot_info(mem: &[u8]) -> Vec<u8> {
+    let mut mem = mem.to_vec();
+    mem[BOOT_INFO as usize..BOOT_INFO as usize + 14].fill(0);
+    let code = build::PARTS
+        .iter()
+        .map(|&(section, _)| object().section(section).unwrap().size)
+        .sum::<u32>();
+    mem[RESET as usize..(RESET + code) as usize].fill(0);
+    mem
+}
+
+fn ram_writes(m: &Machine) -> Vec<u32> {
+    m.writes().into_iter().filter(|&a| a >= RAM.0).collect()
+}
+
+fn check(layout: Layout) -> Machine {
+    let mut m = machine(&layout);
+    let expected = reference(&layout, &m.mem);
+
+    assert_eq!(reset(&mut m), Ok(Exit::Branch("main".into())));
+    assert_eq!(m.calls(), ["PreInit"]);
+    assert_eq!(m.regs[1], RAM.1);
+    assert!(
+        without_boot_info(&m.mem) == without_boot_info(&expected),
+        "RAM image differs from byte-wise initialization"
+    );
+    m
+}
+
+#[test]
+fn bss_and_data() {
+    let m = check(Layout {
+        sbss: 0x0200,
+        ebss: 0x0212,
+        sdata: 0x0212,
+        edata: 0x0230,
+        sramtext: 0x0230,
+        eramtext: 0x0230,
+    });
+
+    // One store per word
+    assert_eq!(ram_writes(&m).len(), (0x30) / 2);
+}
+
+#[test]
+fn bss_only() {
+    check(Layout {
+        sbss: 0x0200,
+        ebss: 0x0204,
+        sdata: 0x0204,
+        edata: 0x0204,
+        sramtext: 0x0204,
+        eramtext: 0x0204,
+    });
+}
+
+#[test]
+fn data_only() {
+    check(Layout {
+        sbss: 0x0200,
+        ebss: 0x0200,
+        sdata: 0x0200,
+        edata: 0x0202,
+        sramtext: 0x0202,
+        eramtext: 0x0202,
+    });
+}
+
+#[test]
+fn empty_sections() {
+    let m = check(Layout {
+        sbss: 0x0200,
+        ebss: 0x0200,
+        sdata: 0x0200,
+        edata: 0x0200,
+        sramtext: 0x0200,
+        eramtext: 0x0200,
+    });
+
+    assert!(ram_writes(&m).is_empty());
+}
+
+#[test]
+fn whole_ram() {
+    check(Layout {
+        sbss: RAM.0,
+        ebss: 0x0300,
+        sdata: 0x0300,
+        edata: RAM.1,
+        sramtext: RAM.1,
+        eramtext: RAM.1,
+    });
+}
+
+#[test]
+fn ramtext() {
+    let m = check(Layout {
+        sbss: 0x0200,
+        ebss: 0x0204,
+        sdata: 0x0204,
+        edata: 0x0208,
+        sramtext: 0x0208,
+        eramtext: 0x0240,
+    });
+
+    assert_eq!(ram_writes(&m).len(), 0x40 / 2);
+}
+
+#[test]
+fn watchdog_untouched_by_default() {
+    let mut m = machine(&Layout {
+        sbss: 0x0200,
+        ebss: 0x0200,
+        sdata: 0x0200,
+        edata: 0x0200,
+        sramtext: 0x0200,
+        eramtext: 0x0200,
+    });
+
+    reset(&mut m).unwrap();
+    assert!(m.writes().is_empty());
+}
+
+#[test]
+fn watchdog_held_before_pre_init() {
+    for &wdtctl in &[0x0120, 0x015c] {
+        let mut m = machine(&Layout {
+            sbss: 0x0200,
+            ebss: 0x0202,
+            sdata: 0x0202,
+            edata: 0x0202,
+            sramtext: 0x0202,
+            eramtext: 0x0202,
+        });
+        m.symbol("__WDTCTL_ADDR", wdtctl);
+
+        reset(&mut m).unwrap();
+        let events = m
+            .events
+            .iter()
+            .filter(|e| !matches!(e, Event::Write(a) if *a < RAM.0 && *a != wdtctl));
+        assert!(events.take(3).eq(&[
+            Event::Write(wdtctl),
+            Event::Call("PreInit".into()),
+            Event::Write(0x0200)
+        ]));
+        assert_eq!(m.word(wdtctl), 0x5a80);
+    }
+}
+
+#[test]
+fn stack_painted_before_pre_init() {
+    let layout = Layout {
+        sbss: 0x0200,
+        ebss: 0x0210,
+        sdata: 0x0210,
+        edata: 0x0220,
+        sramtext: 0x0220,
+        eramtext: 0x0220,
+    };
+    let mut m = machine(&layout);
+    let mut expected = reference(&layout, &m.mem);
+    for a in (0x0300..RAM.1).step_by(2) {
+        expected[a as usize..a as usize + 2].copy_from_slice(&[0xcd, 0xcd]);
+    }
+    m.symbol("_spaint", 0x0300);
+
+    reset_with(&mut m, &["paint-stack"]).unwrap();
+    // The return address of the `PreInit` call lands on the painted stack
+    let sp = RAM.1 as usize - 2;
+    expected[sp..sp + 2].copy_from_slice(&m.mem[sp..sp + 2]);
+    assert!(
+        without_boot_info(&m.mem) == without_boot_info(&expected),
+        "RAM image differs"
+    );
+    assert!(ram_writes(&m)[..0x80]
+        .iter()
+        .copied()
+        .eq((0x0300..RAM.1).step_by(2)));
+    assert_eq!(m.calls(), ["PreInit"]);
+}
+
+#[test]
+fn bss2_and_data2() {
+    const RAM2: (u32, u32) = (0x2c00, 0x2c40);
+    const SIDATA2: u32 = 0xc300;
+
+    let mut m = machine(&Layout {
+        sbss: 0x0200,
+        ebss: 0x0210,
+        sdata: 0x0210,
+        edata: 0x0220,
+        sramtext: 0x0220,
+        eramtext: 0x0220,
+    });
+    let garbage = (RAM2.0..RAM2.1).map(|a| (a as u8).wrapping_mul(5) | 1);
+    m.load(RAM2.0, &garbage.collect::<Vec<_>>());
+    let init = (0..0x20).map(|i| 0x30 ^ i as u8);
+    m.load(SIDATA2, &init.collect::<Vec<_>>());
+    tables(
+        &mut m,
+        &[(0x0200, 0x10), (RAM2.0, 0x12)],
+        &[
+            (SIDATA, 0x0210, 0x10),
+            (SIRAMTEXT, 0x0220, 0),
+            (SIDATA2, 0x2c12, 0x20),
+        ],
+    );
+
+    assert_eq!(reset(&mut m), Ok(Exit::Branch("main".into())));
+    assert!(m.mem[0x2c00..0x2c12].iter().all(|&b| b == 0));
+    assert_eq!(m.mem[0x2c12..0x2c32], m.mem[0xc300..0xc320]);
+    // The rest of RAM2 is left alone
+    assert_eq!(m.mem[0x2c32], (0x32u8).wrapping_mul(5) | 1);
+}
+
+#[test]
+fn upper_init_called_after_ram_init() {
+    let mut m = machine(&Layout {
+        sbss: 0x0200,
+        ebss: 0x0210,
+        sdata: 0x0210,
+        edata: 0x0220,
+        sramtext: 0x0220,
+        eramtext: 0x0230,
+    });
+    m.stub("UpperInit", UPPER_INIT);
+
+    assert_eq!(
+        reset_with(&mut m, &["upper-memory"]),
+        Ok(Exit::Branch("main".into()))
+    );
+    assert_eq!(m.calls(), ["PreInit", "UpperInit"]);
+    assert_eq!(m.events.last(), Some(&Event::Call("UpperInit".into())));
+}
+
+#[test]
+fn upper_init_walks_upper_tables() {
+    const HIRAM: (u32, u32) = (0x1_0000, 0x1_0100);
+    const SIUPPER_DATA: u32 = 0x2_0000;
+    const UPPER_TABLES: u32 = 0xc080;
+
+    let mut m = machine(&Layout {
+        sbss: 0x0200,
+        ebss: 0x0210,
+        sdata: 0x0210,
+        edata: 0x0220,
+        sramtext: 0x0220,
+        eramtext: 0x0220,
+    });
+    let garbage = (HIRAM.0..HIRAM.1).map(|a| (a as u8).wrapping_mul(3) | 1);
+    m.load(HIRAM.0, &garbage.collect::<Vec<_>>());
+    let init = (0..0x40).map(|i| 0x60 ^ i as u8);
+    m.load(SIUPPER_DATA, &init.collect::<Vec<_>>());
+    // .upper.bss at 0x10000..0x10024 and .upper.data at 0x10024..0x10064, in 32-bit entries
+    let entries: [u32; 5] = [HIRAM.0, 0x24, SIUPPER_DATA, 0x1_0024, 0x40];
+    let bytes = entries
+        .iter()
+        .flat_map(|e| e.to_le_bytes())
+        .collect::<Vec<_>>();
+    m.load(UPPER_TABLES, &bytes);
+    m.symbol("__upper_zero_table_start", UPPER_TABLES)
+        .symbol("__upper_zero_table_end", UPPER_TABLES + 8)
+        .symbol("__upper_copy_table_start", UPPER_TABLES + 8)
+        .symbol("__upper_copy_table_end", UPPER_TABLES + 20);
+    m.link(&object(), UPPER_INIT, &[".text.UpperInit"]).unwrap();
+
+    assert_eq!(
+        reset_with(&mut m, &["upper-memory"]),
+        Ok(Exit::Branch("main".into()))
+    );
+    assert!(m.mem[0x1_0000..0x1_0024].iter().all(|&b| b == 0));
+    assert_eq!(m.mem[0x1_0024..0x1_0064], m.mem[0x2_0000..0x2_0040]);
+    // The rest of HIRAM, and what's at the same addresses in the lower 64 KB, is left alone
+    assert_eq!(m.mem[0x1_0064], (0x64u8).wrapping_mul(3) | 1);
+    assert!(m
+        .writes()
+        .iter()
+        .all(|&a| a < 0x1_0000 || (0x1_0000..0x1_0064).contains(&a)));
+    assert!(!m.writes().iter().any(|&a| (0x0000..0x0064).contains(&a)));
+}
+
+#[test]
+fn mpu_enabled_before_main() {
+    const MPUCTL0: u32 = 0x05a0;
+
+    let mut m = machine(&Layout {
+        sbss: 0x0200,
+        ebss: 0x0210,
+        sdata: 0x0210,
+        edata: 0x0220,
+        sramtext: 0x0220,
+        eramtext: 0x0220,
+    });
+    m.stub("UpperInit", UPPER_INIT)
+        .symbol("__MPUCTL0_ADDR", MPUCTL0)
+        .symbol("__MPUSEGB1", 0x0c40)
+        .symbol("__MPUSEGB2", 0x0c80)
+        .symbol("__MPUSAM", 0x3535);
+
+    assert_eq!(
+        reset_with(&mut m, &["upper-memory", "fram-mpu"]),
+        Ok(Exit::Branch("main".into()))
+    );
+    assert_eq!(m.word(MPUCTL0 + 4), 0x0c80); // MPUSEGB2
+    assert_eq!(m.word(MPUCTL0 + 6), 0x0c40); // MPUSEGB1
+    assert_eq!(m.word(MPUCTL0 + 8), 0x3535); // MPUSAM
+
+    // Unlocked, configured, enabled, then locked again, after everything else
+    let mpu_writes = [0, 4, 6, 8, 0, 1].map(|offset| Event::Write(MPUCTL0 + offset));
+    let tail = &m.events[m.events.len() - mpu_writes.len() - 1..];
+    assert_eq!(tail[0], Event::Call("UpperInit".into()));
+    assert_eq!(tail[1..], mpu_writes);
+    assert_eq!(m.word(MPUCTL0), 0x0001); // MPUENA, and not the password
+}
+
+fn boot_info(m: &Machine) -> [u16; 7] {
+    let mut words = [0; 7];
+    for (i, w) in words.iter_mut().enumerate() {
+        *w = m.word(BOOT_INFO + 2 * i as u32);
+    }
+    words
+}
+
+#[test]
+fn boot_info_cold_then_warm() {
+    let layout = Layout {
+        sbss: 0x0200,
+        ebss: 0x0200,
+        sdata: 0x0200,
+        edata: 0x0200,
+        sramtext: 0x0200,
+        eramtext: 0x0200,
+    };
+    let mut m = machine(&layout);
+    m.load(BOOT_INFO, &[0x55; 14]);
+    m.regs[1] = 0x1234;
+
+    reset_with(&mut m, &["boot-info"]).unwrap();
+    assert_eq!(boot_info(&m), [0xb007, 0, 1, 0, 0x1234, 0, 0]);
+
+    // A warm reset, by jumping to `Reset` with interrupts enabled
+    for n in 1..=3 {
+        m.regs[1] = 0x03f0;
+        m.regs[2] = 0x0008;
+        reset_with(&mut m, &["boot-info"]).unwrap();
+        assert_eq!(boot_info(&m), [0xb007, n, 0, 0x0008, 0x03f0, 0, 0]);
+    }
+}
+
+const RETAIN_CHECKSUM: u32 = 0xc400;
+const RETAIN_TABLES: u32 = 0xc0c0;
+const SEAL_RAM: u32 = 0xc704;
+
+// Links `__RetainChecksum` with the layout tag `tag` and retain tables for `sections` and
+// `upper_sections`, as (start, end) pairs
+fn retain_tables(m: &mut Machine, tag: u16, sections: &[(u32, u32)], upper: &[(u32, u32)]) {
+    let words = sections
+        .iter()
+        .flat_map(|&(start, end)| [start as u16, (end - start) as u16])
+        .flat_map(u16::to_le_bytes);
+    let upper_words = upper
+        .iter()
+        .flat_map(|&(start, end)| [start, end - start])
+        .flat_map(u32::to_le_bytes);
+    let bytes = words.chain(upper_words).collect::<Vec<_>>();
+    m.load(RETAIN_TABLES, &bytes);
+
+    let upper_start = RETAIN_TABLES + 4 * sections.len() as u32;
+    m.symbol("__RETAIN_TAG", u32::from(tag))
+        .symbol("__retain_table_start", RETAIN_TABLES)
+        .symbol("__retain_table_end", upper_start)
+        .symbol("__upper_retain_table_start", upper_start)
+        .symbol(
+            "__upper_retain_table_end",
+            RETAIN_TABLES + bytes.len() as u32,
+        );
+    m.link(&object(), RETAIN_CHECKSUM, &[".text.__RetainChecksum"])
+        .unwrap();
+}
+
+// Calls `__RetainChecksum` the way `seal_ram` does and returns the result
+fn retain_checksum(m: &mut Machine) -> u16 {
+    m.stub("seal_ram", SEAL_RAM);
+    m.regs[1] = RAM.1 - 2;
+    m.load(RAM.1 - 2, &(SEAL_RAM as u16).to_le_bytes());
+    assert_eq!(
+        m.run("__RetainChecksum"),
+        Ok(Exit::Branch("seal_ram".into()))
+    );
+    m.regs[12] as u16
+}
+
+// What `__RetainChecksum` should compute over `sections`
+fn reference_checksum(m: &Machine, tag: u16, sections: &[(u32, u32)]) -> u16 {
+    let words = sections
+        .iter()
+        .flat_map(|&(start, end)| (start..end).step_by(2));
+    words.fold(tag, |sum, a| sum.rotate_left(1) ^ m.word(a))
+}
+
+#[test]
+fn ram_retained_across_warm_reset() {
+    const TAG: u16 = 0x1234;
+
+    let layout = Layout {
+        sbss: 0x0200,
+        ebss: 0x0210,
+        sdata: 0x0210,
+        edata: 0x0220,
+        sramtext: 0x0220,
+        eramtext: 0x0230,
+    };
+    let retained = [(0x0200, 0x0210), (0x0210, 0x0220)];
+    let mut m = machine(&layout);
+    retain_tables(&mut m, TAG, &retained, &[]);
+    m.symbol("__RAM_RETENTION", RETAIN_AREA)
+        .symbol("_siramtext", SIRAMTEXT)
+        .symbol("_sramtext", layout.sramtext)
+        .symbol("_eramtext", layout.eramtext);
+    m.load(RETAIN_AREA, &[0x55; 4]);
+    let expected = reference(&layout, &m.mem);
+
+    // Cold boot: RAM is initialized, then marked as such. The calls to `__RetainChecksum` leave a
+    // return address at the top of the stack
+    reset_with(&mut m, &["boot-info", "retain-ram"]).unwrap();
+    let ram = RAM.0 as usize..RAM.1 as usize - 2;
+    assert!(m.mem[ram.clone()] == expected[ram]);
+    assert_eq!(
+        [m.word(RETAIN_AREA), m.word(RETAIN_AREA + 2)],
+        [0x5afe, reference_checksum(&m, TAG, &retained)]
+    );
+    assert_eq!(boot_info(&m)[5], 0);
+
+    // The program changes its statics and seals them, then a warm reset keeps them. `.ramtext` is
+    // code, so it's copied again
+    m.load(0x0200, &[0xaa; 0x30]);
+    let checksum = retain_checksum(&mut m);
+    assert_eq!(checksum, reference_checksum(&m, TAG, &retained));
+    m.load(RETAIN_AREA + 2, &checksum.to_le_bytes());
+    reset_with(&mut m, &["boot-info", "retain-ram"]).unwrap();
+    assert!(m.mem[0x0200..0x0220].iter().all(|&b| b == 0xaa));
+    assert_eq!(m.mem[0x0220..0x0230], expected[0x0220..0x0230]);
+    assert_eq!(boot_info(&m)[5], 1);
+
+    // A change that wasn't sealed, here to a single byte, invalidates the checksum
+    m.load(0x0212, &[0xab]);
+    reset_with(&mut m, &["boot-info", "retain-ram"]).unwrap();
+    assert!(m.mem[0x0200..0x0210].iter().all(|&b| b == 0));
+    assert_eq!(m.mem[0x0210..0x0220], expected[0x0210..0x0220]);
+    assert_eq!(boot_info(&m)[5], 0);
+
+    // Another program with the same layout keeps the statics, but runs its own `.ramtext`
+    m.load(SIRAMTEXT, &[0x3c; 0x10]);
+    reset_with(&mut m, &["boot-info", "retain-ram"]).unwrap();
+    assert_eq!(m.mem[0x0200..0x0220], expected[0x0200..0x0220]);
+    assert!(m.mem[0x0220..0x0230].iter().all(|&b| b == 0x3c));
+    assert_eq!(boot_info(&m)[5], 1);
+
+    // A different RAM layout changes the checksum too
+    m.load(0x0200, &[0xaa; 0x10]);
+    let checksum = retain_checksum(&mut m);
+    m.load(RETAIN_AREA + 2, &checksum.to_le_bytes());
+    retain_tables(&mut m, TAG + 1, &retained, &[]);
+    reset_with(&mut m, &["boot-info", "retain-ram"]).unwrap();
+    assert!(m.mem[0x0200..0x0210].iter().all(|&b| b == 0));
+    assert_eq!(boot_info(&m)[5], 0);
+}
+
+#[test]
+fn retain_checksum_covers_upper_sections() {
+    const HIRAM: (u32, u32) = (0x1_0000, 0x1_0100);
+
+    let mut m = machine(&Layout {
+        sbss: 0x0200,
+        ebss: 0x0210,
+        sdata: 0x0210,
+        edata: 0x0220,
+        sramtext: 0x0220,
+        eramtext: 0x0220,
+    });
+    let garbage = (HIRAM.0..HIRAM.1).map(|a| (a as u8).wrapping_mul(3) | 1);
+    m.load(HIRAM.0, &garbage.collect::<Vec<_>>());
+    let sections = [(0x0200, 0x0220), (0x1_0000, 0x1_0024), (0x1_0024, 0x1_0064)];
+    retain_tables(&mut m, 0x1234, &sections[..1], &sections[1..]);
+    m.regs[4..12].copy_from_slice(&[0x1_4444; 8]);
+
+    let checksum = retain_checksum(&mut m);
+    assert_eq!(checksum, reference_checksum(&m, 0x1234, &sections));
+    // It only uses the registers that a C function may clobber, and writes nothing
+    assert_eq!(m.regs[4..12], [0x1_4444; 8]);
+    assert_eq!(m.writes(), []);
+
+    // Every word counts, including the last one of the upper sections
+    m.load(0x1_0062, &[0]);
+    assert_ne!(retain_checksum(&mut m), checksum);
+}
+
+#[test]
+fn lpm5_wakeup_called_before_ram_init() {
+    const SYSRSTIV: u32 = 0x019e;
+
+    // A wake-up from LPMx.5, a reset from the RST pin and a power-up
+    for &(cause, calls) in &[
+        (0x08, &["Lpm5Wakeup", "PreInit"][..]),
+        (0x04, &["PreInit"][..]),
+        (0x02, &["PreInit"][..]),
+    ] {
+        let mut m = machine(&Layout {
+            sbss: 0x0200,
+            ebss: 0x0210,
+            sdata: 0x0210,
+            edata: 0x0220,
+            sramtext: 0x0220,
+            eramtext: 0x0230,
+        });
+        m.load(SYSRSTIV, &u16::to_le_bytes(cause));
+        m.symbol("__SYSRSTIV", SYSRSTIV)
+            .stub("Lpm5Wakeup", LPM5_WAKEUP);
+
+        assert_eq!(
+            reset_with(&mut m, &["boot-info", "lpm5-wakeup"]),
+            Ok(Exit::Branch("main".into()))
+        );
+        assert_eq!(m.calls(), calls);
+        assert_eq!(boot_info(&m)[6], cause);
+        // Only the boot info is written before the wake-up handler runs
+        let call = m
+            .events
+            .iter()
+            .position(|e| *e == Event::Call(calls[0].into()));
+        assert!(m.events[..call.unwrap()]
+            .iter()
+            .all(|e| matches!(e, Event::Write(a) if (BOOT_INFO..BOOT_INFO + 14).contains(a))));
+    }
+}
+
+#[test]
+fn lpm5_wakeup_needs_its_feature() {
+    const SYSRSTIV: u32 = 0x019e;
+
+    let mut m = machine(&Layout {
+        sbss: 0x0200,
+        ebss: 0x0200,
+        sdata: 0x0200,
+        edata: 0x0200,
+        sramtext: 0x0200,
+        eramtext: 0x0200,
+    });
+    m.load(SYSRSTIV, &u16::to_le_bytes(0x08));
+    m.symbol("__SYSRSTIV", SYSRSTIV)
+        .stub("Lpm5Wakeup", LPM5_WAKEUP);
+
+    // `boot-info` reads the reset cause, but only `lpm5-wakeup` acts on it
+    reset_with(&mut m, &["boot-info"]).unwrap();
+    assert_eq!(m.calls(), ["PreInit"]);
+    assert_eq!(boot_info(&m)[6], 0x08);
+}
+
+#[test]
+fn ram_and_registers_scrubbed() {
+    let layout = Layout {
+        sbss: 0x0200,
+        ebss: 0x0210,
+        sdata: 0x0210,
+        edata: 0x0220,
+        sramtext: 0x0220,
+        eramtext: 0x0230,
+    };
+    let mut m = machine(&layout);
+    // __BOOT_INFO in RAM, left over from a warm reset
+    m.symbol("__BOOT_INFO", 0x03e0)
+        .symbol("_sscrub", RAM.0)
+        .symbol("_escrub", RAM.1);
+    m.load(0x03e0, &u16::to_le_bytes(0xb007));
+    m.regs[1] = 0x1234;
+    for (r, reg) in m.regs.iter_mut().enumerate().skip(4) {
+        *reg = 0x1100 * r as u32;
+    }
+    let mut expected = reference(&layout, &m.mem);
+    expected[0x0230..RAM.1 as usize].fill(0);
+
+    assert_eq!(
+        reset_with(&mut m, &["boot-info", "scrub-ram"]),
+        Ok(Exit::Branch("main".into()))
+    );
+    assert_eq!(m.regs[4..], [0; 12]);
+    // Nothing is left but the statics and a cold boot's info
+    let boot_info = (0..7).map(|i| m.word(0x03e0 + 2 * i)).collect::<Vec<_>>();
+    assert_eq!(boot_info, [0xb007, 0, 1, 0, 0x1234, 0, 0]);
+    m.load(0x03e0, &[0; 14]);
+    let ram = RAM.0 as usize..RAM.1 as usize;
+    assert!(m.mem[ram.clone()] == expected[ram]);
+}
+
+#[test]
+fn scrub_skipped_when_ram_retained() {
+    const TAG: u16 = 0x1234;
+    const FEATURES: &[&str] = &["boot-info", "retain-ram", "scrub-ram"];
+
+    let layout = Layout {
+        sbss: 0x0200,
+        ebss: 0x0210,
+        sdata: 0x0210,
+        edata: 0x0220,
+        sramtext: 0x0220,
+        eramtext: 0x0230,
+    };
+    let mut m = machine(&layout);
+    retain_tables(&mut m, TAG, &[(0x0200, 0x0210), (0x0210, 0x0220)], &[]);
+    // __BOOT_INFO and __RAM_RETENTION in RAM, like the rest of .uninit
+    m.symbol("__BOOT_INFO", 0x03e0)
+        .symbol("__RAM_RETENTION", 0x03f0)
+        .symbol("_sscrub", RAM.0)
+        .symbol("_escrub", RAM.1)
+        .symbol("_siramtext", SIRAMTEXT)
+        .symbol("_sramtext", layout.sramtext)
+        .symbol("_eramtext", layout.eramtext);
+    let boot_info = |m: &Machine| (0..7).map(|i| m.word(0x03e0 + 2 * i)).collect::<Vec<_>>();
+
+    // Cold boot: RAM is scrubbed, then initialized
+    reset_with(&mut m, FEATURES).unwrap();
+    assert!(m.mem[0x0230..0x03e0].iter().all(|&b| b == 0));
+    assert_eq!(boot_info(&m)[1..3], [0, 1]);
+    assert_eq!(boot_info(&m)[5], 0);
+
+    // A warm reset after the program sealed its statics keeps all of RAM, including an `#[uninit]`
+    // static and the boot info
+    m.load(0x0200, &[0xaa; 0x20]).load(0x0300, &[0x77; 0x10]);
+    let checksum = retain_checksum(&mut m);
+    m.load(0x03f2, &checksum.to_le_bytes());
+    reset_with(&mut m, FEATURES).unwrap();
+    assert!(m.mem[0x0200..0x0220].iter().all(|&b| b == 0xaa));
+    assert!(m.mem[0x0300..0x0310].iter().all(|&b| b == 0x77));
+    assert_eq!(boot_info(&m)[1..3], [1, 0]);
+    assert_eq!(boot_info(&m)[5], 1);
+
+    // Without a valid checksum, RAM is scrubbed again
+    m.load(0x0200, &[0xab]);
+    reset_with(&mut m, FEATURES).unwrap();
+    assert!(m.mem[0x0200..0x0210].iter().all(|&b| b == 0));
+    assert!(m.mem[0x0300..0x0310].iter().all(|&b| b == 0));
+    assert_eq!(boot_info(&m)[1..3], [0, 1]);
+    assert_eq!(boot_info(&m)[5], 0);
+    assert_eq!(m.regs[4..], [0; 12]);
+}
+
+// The MSP430X variant of the blob only differs from the MSP430 one in how the default interrupt
+// handler saves registers (see interrupts.rs), so the tests here cover its `Reset` and `UpperInit`
+// as well
+#[test]
+fn cpux_variant_has_the_same_reset() {
+    let cpux = Object::from_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../bin/msp430x-none-elf.a"
+    ))
+    .unwrap();
+    for section in object().sections().iter().filter(|s| s.alloc) {
+        if section.name != ".text.__DefaultDispatch" {
+            let other = cpux.section(&section.name).unwrap();
+            assert_eq!(other.data, section.data, "{}", section.name);
+            assert_eq!(other.size, section.size, "{}", section.name);
+        }
+    }
+}
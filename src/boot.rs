@@ -0,0 +1,103 @@
+//! Information about the current boot, collected by `Reset`
+
+use core::ptr;
+
+extern "C" {
+    // Defined in asm.s. Lives in .uninit, so it survives resets as long as RAM is powered
+    static __BOOT_INFO: [u16; 7];
+}
+
+/// Information about the current boot
+///
+/// `Reset` fills this in before calling `PreInit`, using a few words of RAM that it never
+/// initializes. The entry point can take it as an argument (see [`#[entry]`](crate::entry)), or
+/// the program can call [`BootInfo::get`] at any time.
+///
+/// A cold boot is detected by the absence of a magic word in RAM, so on a power-up there's a small
+/// (1 in 65536) chance that leftover RAM contents are mistaken for a warm reset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BootInfo {
+    reset_count: u16,
+    cold_boot: bool,
+    sr: u16,
+    sp: u16,
+    ram_retained: bool,
+    sysrstiv: u16,
+}
+
+impl BootInfo {
+    /// Returns the information `Reset` collected during the current boot
+    pub fn get() -> Self {
+        // The first word is the magic word `Reset` uses to recognize a warm reset
+        let [_, reset_count, cold_boot, sr, sp, ram_retained, sysrstiv] =
+            unsafe { ptr::read_volatile(ptr::addr_of!(__BOOT_INFO)) };
+
+        BootInfo {
+            reset_count,
+            cold_boot: cold_boot != 0,
+            sr,
+            sp,
+            ram_retained: ram_retained != 0,
+            sysrstiv,
+        }
+    }
+
+    /// Number of resets since the last cold boot
+    ///
+    /// This is 0 on a cold boot and wraps around after 65535 warm resets.
+    pub fn reset_count(&self) -> u16 {
+        self.reset_count
+    }
+
+    /// Returns `true` if RAM didn't hold its contents from a previous boot, e.g. after a power-up
+    pub fn is_cold_boot(&self) -> bool {
+        self.cold_boot
+    }
+
+    /// Returns `true` if RAM kept its contents from the previous boot, e.g. after a watchdog or
+    /// software reset
+    pub fn is_warm_reset(&self) -> bool {
+        !self.cold_boot
+    }
+
+    /// Value of the status register when `Reset` started
+    ///
+    /// A hardware reset clears the status register, so a non-zero value means that something
+    /// jumped to `Reset` instead.
+    pub fn sr(&self) -> u16 {
+        self.sr
+    }
+
+    /// Value of the stack pointer when `Reset` started
+    ///
+    /// This is only meaningful if `Reset` was entered by a jump rather than a hardware reset.
+    pub fn sp(&self) -> u16 {
+        self.sp
+    }
+
+    /// Returns `true` if `Reset` skipped the RAM init and left the statics with the values they had
+    /// before the reset
+    ///
+    /// This is only ever the case with the `retain-ram` feature, after a warm reset of a program
+    /// whose RAM init had completed. Otherwise `Reset` zeroed and initialized the statics as usual.
+    pub fn is_ram_retained(&self) -> bool {
+        self.ram_retained
+    }
+
+    /// Value that `Reset` read from `SYSRSTIV`, the highest priority cause of the reset
+    ///
+    /// Reading `SYSRSTIV` clears that cause, so the program should use this value instead. It's 0
+    /// if `memory.x` doesn't set `__SYSRSTIV_ADDR`.
+    pub fn sysrstiv(&self) -> u16 {
+        self.sysrstiv
+    }
+
+    /// Returns `true` if this boot is a wake-up from LPMx.5 (LPM3.5 or LPM4.5)
+    ///
+    /// With the `lpm5-wakeup` feature, `Reset` has already called the `#[lpm5_wakeup]` handler in
+    /// that case, and the handler returned.
+    pub fn is_lpm5_wakeup(&self) -> bool {
+        // SYSRSTIV_LPM5WU
+        self.sysrstiv == 0x08
+    }
+}
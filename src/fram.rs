@@ -0,0 +1,112 @@
+//! Variables that keep their value across resets and power cycles (FRAM devices only)
+//!
+//! A `static` marked with the [`#[persistent]`](crate::persistent) attribute is placed in the
+//! `.persistent` section, which lives in FRAM next to `.rodata`. Its initial value is written
+//! when the device is programmed; `Reset` never initializes it again.
+//!
+//! On FR2xx and FR4xx devices, program FRAM is write protected by the `PFWP` bit of `SYSCFG0`.
+//! [`Persistent::set`] and [`unprotected`] clear that bit around the write and restore it
+//! afterwards, provided `memory.x` tells `msp430-rt` where `SYSCFG0` is:
+//!
+//! ``` text
+//! /* FR2xx and FR4xx devices */
+//! __SYSCFG0_ADDR = 0x0160;
+//! ```
+//!
+//! When `__SYSCFG0_ADDR` is not set, the write protection bits are left alone. This is what you
+//! want on FR5xx and FR6xx devices, which protect FRAM with the MPU instead (see the `fram-mpu`
+//! feature).
+//!
+//! # Example
+//!
+//! ``` ignore
+//! use msp430_rt::{fram::Persistent, persistent};
+//!
+//! #[persistent]
+//! static BOOT_COUNT: Persistent<u16> = Persistent::new(0);
+//!
+//! fn on_boot() {
+//!     BOOT_COUNT.set(BOOT_COUNT.get() + 1);
+//! }
+//! ```
+
+use core::{cell::UnsafeCell, ptr};
+use msp430::{interrupt, register::sr};
+
+/// `SYSCFG0` password
+const FRWPPW: u16 = 0xa500;
+/// Program FRAM write protection
+const PFWP: u16 = 1 << 0;
+
+extern "C" {
+    // Weak so the address can be checked against 0 (the default provided by link.x)
+    #[linkage = "extern_weak"]
+    static __SYSCFG0_ADDR: *mut u16;
+}
+
+/// A value stored in FRAM
+///
+/// Reads and writes happen with interrupts disabled, so a `Persistent` can be shared between the
+/// entry point and interrupt handlers.
+#[repr(transparent)]
+pub struct Persistent<T> {
+    value: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for Persistent<T> {}
+
+impl<T> Persistent<T> {
+    /// Creates a new `Persistent` with the value it holds after the device is programmed
+    pub const fn new(value: T) -> Self {
+        Persistent {
+            value: UnsafeCell::new(value),
+        }
+    }
+}
+
+impl<T: Copy> Persistent<T> {
+    /// Returns the stored value
+    #[inline]
+    pub fn get(&self) -> T {
+        free(|| unsafe { ptr::read_volatile(self.value.get()) })
+    }
+
+    /// Stores `value`, lifting the FRAM write protection for the duration of the write
+    #[inline]
+    pub fn set(&self, value: T) {
+        unprotected(|| unsafe { ptr::write_volatile(self.value.get(), value) })
+    }
+}
+
+/// Runs `f` with interrupts disabled and the program FRAM write protection (`PFWP`) lifted
+///
+/// The previous state of `SYSCFG0` is restored when `f` returns. If `__SYSCFG0_ADDR` is not set,
+/// `SYSCFG0` is not touched.
+pub fn unprotected<R>(f: impl FnOnce() -> R) -> R {
+    let syscfg0 = unsafe { __SYSCFG0_ADDR };
+
+    free(|| unsafe {
+        if syscfg0.is_null() {
+            return f();
+        }
+
+        // The upper byte doesn't read back as the password
+        let saved = ptr::read_volatile(syscfg0) & 0xff;
+        ptr::write_volatile(syscfg0, FRWPPW | (saved & !PFWP));
+        let r = f();
+        ptr::write_volatile(syscfg0, FRWPPW | saved);
+        r
+    })
+}
+
+// Runs `f` with interrupts disabled, then restores the previous interrupt state
+#[inline(always)]
+fn free<R>(f: impl FnOnce() -> R) -> R {
+    let status = sr::read();
+    interrupt::disable();
+    let r = f();
+    if status.gie() {
+        unsafe { interrupt::enable() }
+    }
+    r
+}
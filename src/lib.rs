@@ -19,6 +19,13 @@
 //!
 //! - `#[entry]` to declare the entry point of the program
 //! - `#[pre_init]` to run code *before* `static` variables are initialized
+//! - `#[lpm5_wakeup]` to handle a wake-up from LPMx.5 before `static` variables are initialized
+//!   (with the `lpm5-wakeup` feature)
+//! - `#[uninit]` to place a `static mut` in RAM that is never initialized
+//! - `#[persistent]` to place a `static` in FRAM that survives resets (see [`fram`])
+//! - `#[ramfunc]` to run a function, or an interrupt handler, from RAM
+//! - `#[info_mem]` to place a `static` in a segment of the information memory
+//! - `#[ram2]` to place a `static` in a second RAM region
 //!
 //! This crate also implements a related attribute called `#[interrupt]`, which allows you
 //! to define interrupt handlers. However, since which interrupts are available depends on the
@@ -38,11 +45,11 @@
 //!
 //! The linker script must specify the memory available in the device as, at least, three `MEMORY`
 //! regions: one named `ROM`, one named `RAM`, and one named `VECTORS`. The `.text` and `.rodata`
-//! sections of the program will be placed in the `ROM` region, whereas the `.bss` and `.data`
-//! sections, as well as the heap, will be placed in the `RAM` region. The `.vector_table` section,
-//! which including the interrupt vectors and reset address, will be placed in the `VECTORS`
-//! region at the end of flash. The `ROM` region should end at the address the `VECTORS` region
-//! begins.
+//! sections of the program will be placed in the `ROM` region, whereas the `.bss`, `.data` and
+//! `.uninit` sections, as well as the heap, will be placed in the `RAM` region. The
+//! `.vector_table` section, which including the interrupt vectors and reset address, will be
+//! placed in the `VECTORS` region at the end of flash. The `ROM` region should end at the address
+//! the `VECTORS` region begins.
 //!
 //! A `VECTORS` region is required because between (_and within_) msp430 device families:
 //! * Devices do not have a constant single vector table size.
@@ -69,6 +76,67 @@
 //! you can re-configure the expected end address by setting `__VECTORS_END_ADDR` in your
 //! `memory.x` file.
 //!
+//! ### `__WDTCTL_ADDR`
+//!
+//! The watchdog timer is running when the device comes out of reset. On devices with a lot of RAM
+//! it may time out while `Reset` is still initializing `.bss` and `.data`, resetting the device
+//! over and over. If `memory.x` sets `__WDTCTL_ADDR` to the address of the `WDTCTL` register,
+//! `Reset` holds the watchdog before calling `PreInit`. The address depends on the device family:
+//!
+//! ``` text
+//! /* x1xx, x2xx (including G2) and x4xx devices */
+//! __WDTCTL_ADDR = 0x0120;
+//!
+//! /* x5xx, x6xx and FRxx devices */
+//! __WDTCTL_ADDR = 0x015C;
+//! ```
+//!
+//! When `__WDTCTL_ADDR` is not set, `Reset` leaves the watchdog alone, and it's up to `#[pre_init]`
+//! or `main` to service or stop it.
+//!
+//! ### `__SYSRSTIV_ADDR`
+//!
+//! On x5xx, x6xx and FRxx devices, the `SYSRSTIV` register tells why the device reset, e.g. a
+//! watchdog timeout or a wake-up from LPMx.5. If `memory.x` sets `__SYSRSTIV_ADDR` to its address,
+//! `Reset` reads it with the `boot-info` feature and keeps the value in [`BootInfo::sysrstiv`].
+//! The `lpm5-wakeup` feature requires it. Without `boot-info`, `Reset` leaves `SYSRSTIV` alone.
+//!
+//! ``` text
+//! /* x5xx, x6xx and FRxx devices */
+//! __SYSRSTIV_ADDR = 0x019E;
+//! ```
+//!
+//! ### `_stack_start` and `_stack_size`
+//!
+//! The stack grows down from `_stack_start`, which defaults to the end of the `RAM` region. Nothing
+//! keeps it from running into the statics below it, so `memory.x` can reserve a minimum amount of
+//! stack with `_stack_size`. Linking then fails if `.bss`, `.data`, `.ramtext` and `.uninit` leave
+//! less than `_stack_size` bytes below `_stack_start`, and `ld` reports how many bytes are missing
+//! (``region `RAM' overflowed by N bytes``).
+//!
+//! ``` text
+//! /* Keep at least 128 bytes of RAM for the stack */
+//! _stack_size = 128;
+//! ```
+//!
+//! `_stack_start` can be overridden as well, e.g. to give the stack a memory region of its own.
+//! The `RAM` region then no longer includes the stack, so `ld` itself makes sure the statics don't
+//! overlap it:
+//!
+//! ``` text
+//! MEMORY
+//! {
+//!   RAM : ORIGIN = 0x0200, LENGTH = 0x0180
+//!   STACK : ORIGIN = 0x0380, LENGTH = 0x0080
+//!   ROM : ORIGIN = 0xC000, LENGTH = 0x3FE0
+//!   VECTORS : ORIGIN = 0xFFE0, LENGTH = 0x20
+//! }
+//!
+//! _stack_start = ORIGIN(STACK) + LENGTH(STACK);
+//! ```
+//!
+//! Both `_stack_start` and `_stack_size` must be even.
+//!
 //! # An example
 //!
 //! This section presents a minimal application built on top of `msp430-rt`.
@@ -121,6 +189,31 @@
 //!
 //! # Optional features
 //!
+//! The code that a feature adds to `Reset`, e.g. to scrub RAM, is only linked when the feature is
+//! enabled.
+//!
+//! ## `boot-info`
+//!
+//! Makes `Reset` record how the device booted in 14 bytes of `.uninit`: whether RAM kept its
+//! contents, the number of warm resets since the last cold boot, the SR and SP values that `Reset`
+//! was entered with and, if `memory.x` sets `__SYSRSTIV_ADDR`, the reset cause. [`BootInfo`]
+//! reads them, and the [`#[entry]`](crate::entry) function can take it as its last argument.
+//!
+//! ``` ignore
+//! use msp430_rt::{entry, BootInfo};
+//!
+//! #[entry]
+//! fn main(boot: BootInfo) -> ! {
+//!     if boot.is_warm_reset() {
+//!         /* recover from a watchdog reset, crash counting, .. */
+//!     }
+//!
+//!     loop {
+//!         /* .. */
+//!     }
+//! }
+//! ```
+//!
 //! ## `device`
 //!
 //! If this feature is disabled then this crate populates the whole vector table. All the interrupts
@@ -129,11 +222,280 @@
 //! on any MSP430 device -- provided that you correctly specified its memory layout in `memory.x`
 //! -- without hitting undefined behavior.
 //!
+//! The vector table is 32 bytes long, as on the MSP430G2553, unless the `MSP430_RT_VECTORS_LENGTH`
+//! environment variable sets its length in bytes, which must match the length of the `VECTORS`
+//! region in `memory.x`. This can be done in `.cargo/config.toml`:
+//!
+//! ``` text
+//! [env]
+//! MSP430_RT_VECTORS_LENGTH = "0x80"
+//! ```
+//!
+//! If they don't match, linking fails with the number of vectors that `msp430-rt` generated, and
+//! ld reports the difference as an overflow of the `VECTORS` region, 2 bytes per vector.
+//!
 //! If this feature is enabled then the interrupts section of the vector table is left unpopulated
 //! and some other crate, or the user, will have to populate it. This mode is meant to be used in
 //! conjunction with PAC crates generated using `svd2rust`. Those *PAC crates* will populate the
 //! missing part of the vector table when their `"rt"` feature is enabled.
 //!
+//! ## `fram-mpu`
+//!
+//! Makes `Reset` enable the memory protection unit (MPU) of FR5xx and FR6xx devices right before
+//! calling `main`, so that a stray write can't corrupt the code and constants in FRAM. The MPU
+//! splits the main FRAM in three segments, whose borders `link.x` puts at both ends of the
+//! `.persistent` section, aligned to 1 KB:
+//!
+//! - segment 1, `.text` and `.rodata`: read-only and executable
+//! - segment 2, `.persistent` (see [`#[persistent]`](crate::persistent)): readable and writable
+//! - segment 3, everything after `.persistent`: read-only and executable
+//!
+//! The information memory is readable and writable. The borders are `_spersistent` and
+//! `_epersistent`; the alignment can leave up to 1 KB of unused FRAM before and after
+//! `.persistent`. `memory.x` can change the access rights by setting `__MPUSAM` to the value of
+//! the `MPUSAM` register, e.g. to make a violation reset the device:
+//!
+//! ``` text
+//! /* Like the default (0x3535), but with the violation select bits set */
+//! __MPUSAM = 0xBDBD;
+//! ```
+//!
+//! `Reset` doesn't set `MPULOCK`, so the program can still reconfigure the MPU.
+//!
+//! ## `hold-watchdog`
+//!
+//! Makes `Reset` hold the watchdog before calling `PreInit`. The address of `WDTCTL` still has to
+//! be provided by `memory.x` (see [`__WDTCTL_ADDR`](#__wdtctl_addr)); with this feature enabled,
+//! linking fails if it isn't, instead of silently leaving the watchdog running.
+//!
+//! ## `info-mem`
+//!
+//! Places the `.infoA`, `.infoB`, `.infoC` and `.infoD` sections, which hold the statics marked
+//! with [`#[info_mem]`](crate::info_mem), in the segments of the information memory. `memory.x`
+//! must then define one region per segment, named `INFOA` to `INFOD`:
+//!
+//! ``` text
+//! /* Linker script for the MSP430G2553 */
+//! MEMORY
+//! {
+//!   RAM : ORIGIN = 0x0200, LENGTH = 0x0200
+//!   ROM : ORIGIN = 0xC000, LENGTH = 0x3FE0
+//!   VECTORS : ORIGIN = 0xFFE0, LENGTH = 0x20
+//!   INFOD : ORIGIN = 0x1000, LENGTH = 0x40
+//!   INFOC : ORIGIN = 0x1040, LENGTH = 0x40
+//!   INFOB : ORIGIN = 0x1080, LENGTH = 0x40
+//!   INFOA : ORIGIN = 0x10C0, LENGTH = 0x40
+//! }
+//!
+//! /* Fail to link if anything is placed in INFOA */
+//! __INFOA_UNTOUCHED = 1;
+//! ```
+//!
+//! Like `#[persistent]` statics, these are only initialized when the device is programmed. On
+//! most devices `INFOA` holds the factory calibration data, which erasing or programming the
+//! segment destroys. Setting `__INFOA_UNTOUCHED` to a non-zero value, as above, makes linking fail
+//! if anything ends up in `INFOA`, so that the image leaves it alone.
+//!
+//! ## `ipe`
+//!
+//! Sets up the IP encapsulation (IPE) of FR5xx and FR6xx devices, which keeps a segment of FRAM
+//! from being read or written by anything but the code in it, even over JTAG. `memory.x` must then
+//! define the `IPE` region, which becomes the encapsulated segment and has to start at a multiple
+//! of 1 KB, and the `IPESIGNATURE` region:
+//!
+//! ``` text
+//! /* Linker script for the MSP430FR5969 */
+//! MEMORY
+//! {
+//!   RAM : ORIGIN = 0x1C00, LENGTH = 0x0800
+//!   IPE : ORIGIN = 0x4400, LENGTH = 0x0C00
+//!   ROM : ORIGIN = 0x5000, LENGTH = 0xAF80
+//!   IPESIGNATURE : ORIGIN = 0xFF88, LENGTH = 0x4
+//!   VECTORS : ORIGIN = 0xFF90, LENGTH = 0x70
+//! }
+//! ```
+//!
+//! The segment starts with the IPE init structure (the `.ipestruct` section), which `link.x`
+//! builds from the segment borders, followed by the `.ipe` and `.ipe_const` input sections. Place
+//! code and constants there with `#[link_section = ".ipe.<name>"]`. The segment ends at the next
+//! 1 KB boundary after them. `_sipe` and `_eipe` are the borders of the segment. The IPE signature
+//! points the boot code to the init structure, which it applies after a BOR.
+//!
+//! The structure sets `MPUIPC0` to `__IPE_MPUIPC0`, 0x00C0 (`MPUIPENA | MPUIPLOCK`) by default,
+//! which `memory.x` can override, e.g. to also make a violation reset the device:
+//!
+//! ``` text
+//! __IPE_MPUIPC0 = 0x00E0; /* MPUIPENA | MPUIPLOCK | MPUIPPUC */
+//! ```
+//!
+//! ## `jtag-signature`
+//!
+//! Places the `.jtagsignature` and `.bslsignature` sections, which hold the JTAG and BSL signatures
+//! of FRAM devices, in two more regions that `memory.x` must then define. Each signature is two
+//! words, and `VECTORS` starts right after them:
+//!
+//! ``` text
+//! /* Linker script for the MSP430FR5969 */
+//! MEMORY
+//! {
+//!   RAM : ORIGIN = 0x1C00, LENGTH = 0x0800
+//!   ROM : ORIGIN = 0x4400, LENGTH = 0xBB80
+//!   JTAGSIGNATURE : ORIGIN = 0xFF80, LENGTH = 0x4
+//!   BSLSIGNATURE : ORIGIN = 0xFF84, LENGTH = 0x4
+//!   VECTORS : ORIGIN = 0xFF90, LENGTH = 0x70
+//! }
+//!
+//! /* Lock JTAG and Spy-Bi-Wire access */
+//! __JTAG_SIGNATURE_1 = 0x5555;
+//! __JTAG_SIGNATURE_2 = 0x5555;
+//! ```
+//!
+//! The signature words are set with the `__JTAG_SIGNATURE_1`, `__JTAG_SIGNATURE_2`,
+//! `__BSL_SIGNATURE_1` and `__BSL_SIGNATURE_2` symbols, which default to 0xFFFF: JTAG access and
+//! the BSL stay enabled. Setting both JTAG signature words to 0x5555 locks JTAG access. See the
+//! user's guide of the device family for the other values. Locking JTAG with a password isn't
+//! supported: `msp430-rt` doesn't place the password that this mode needs.
+//!
+//! To keep a development board from being locked by accident, linking fails if a debug build, i.e.
+//! one with a profile based on `dev`, sets either JTAG signature word to anything but 0xFFFF or
+//! 0x0000.
+//!
+//! ## `lpm5-wakeup`
+//!
+//! Makes `Reset` call the [`#[lpm5_wakeup]`](crate::lpm5_wakeup) handler on a wake-up from LPM3.5
+//! or LPM4.5, which goes through the reset vector, before `PreInit` and the RAM init. Linking fails
+//! if the program has no such handler or if `memory.x` doesn't set
+//! [`__SYSRSTIV_ADDR`](crate#__sysrstiv_addr), which `Reset` needs to tell a wake-up from other
+//! resets. This feature enables `boot-info` too: [`BootInfo::is_lpm5_wakeup`] tells the entry
+//! point that the handler ran and returned.
+//!
+//! ## `paint-stack`
+//!
+//! Makes `Reset` fill the stack reservation with a fixed pattern before calling `PreInit`, and
+//! provides [`stack_high_water_mark`], which reports how much of it the program has used so far.
+//! This helps choosing a `_stack_size` (see [`_stack_start` and
+//! `_stack_size`](#_stack_start-and-_stack_size)), which must be set; linking fails otherwise.
+//! Painting takes time proportional to the size of the area, so you may want to enable the
+//! watchdog hold as well.
+//!
+//! ## `ram2`
+//!
+//! Places the `.bss2` and `.data2` sections, which hold the statics marked with
+//! [`#[ram2]`](crate::ram2), in a second RAM region, `RAM2`, that `memory.x` must then define.
+//! Some devices have such a block of RAM apart from the main one:
+//!
+//! ``` text
+//! /* Linker script for the MSP430FR5994 */
+//! MEMORY
+//! {
+//!   RAM : ORIGIN = 0x1C00, LENGTH = 0x1000
+//!   RAM2 : ORIGIN = 0x2C00, LENGTH = 0x1000 /* LEA RAM */
+//!   ROM : ORIGIN = 0x4000, LENGTH = 0xBF80
+//!   VECTORS : ORIGIN = 0xFF80, LENGTH = 0x80
+//! }
+//! ```
+//!
+//! `Reset` zeroes `.bss2` and initializes `.data2` right after `.bss`, `.data` and `.ramtext`.
+//! Without this feature the `#[ram2]` statics go in `.bss` and `.data`.
+//!
+//! ## `retain-ram`
+//!
+//! Makes `Reset` keep the contents of RAM across warm resets, e.g. a watchdog timeout or a software
+//! reset (PUC), instead of zeroing `.bss` and initializing `.data` again. Once the RAM init is
+//! done, `Reset` writes a magic word and a checksum of `.bss` and `.data` in `.uninit`, and
+//! [`seal_ram`] writes the checksum again on request. On the next reset `Reset` skips the RAM init
+//! if the magic word is still there and the checksum matches what's in RAM. That isn't the case
+//! after a power-up, after loading a program with a different RAM layout (the checksum starts from
+//! a tag of the layout), or if RAM changed since the last checksum was written. `.bss2`, `.data2`
+//! and the upper memory sections are covered by the checksum and kept or initialized along with
+//! `.bss` and `.data`. `.ramtext` holds code rather than state, so `Reset` copies it on every
+//! reset.
+//!
+//! So the program has to call [`seal_ram`] after updating the statics it wants to keep, e.g. after
+//! each sample it logs. A reset that comes before the call, e.g. halfway through an update,
+//! initializes RAM again.
+//!
+//! This feature enables `boot-info` too: [`BootInfo::is_ram_retained`] tells which way `Reset`
+//! went. The magic word is cleared before the RAM init starts, so a reset during the RAM init
+//! itself leads to a full RAM init on the next boot.
+//!
+//! ``` ignore
+//! use msp430_rt::{entry, seal_ram, BootInfo};
+//!
+//! static mut SAMPLES: u16 = 0;
+//!
+//! #[entry]
+//! fn main(boot: BootInfo) -> ! {
+//!     if !boot.is_ram_retained() {
+//!         // `SAMPLES` starts over from 0
+//!     }
+//!     loop {
+//!         unsafe { SAMPLES += 1 };
+//!         seal_ram();
+//!     }
+//! }
+//! ```
+//!
+//! ## `scrub-ram`
+//!
+//! Makes `Reset` zero the whole `RAM` region right after holding the watchdog, and clear the
+//! registers R4 to R15 before calling `main`, so that nothing from a previous run or from a
+//! bootloader leaks into the program. This covers the statics marked with `#[uninit]` and the
+//! stack area too. The `RAM2` region, if any, is left alone.
+//!
+//! [`BootInfo`] is in RAM as well, so every boot is reported as a cold boot. With the `retain-ram`
+//! feature too, `Reset` doesn't scrub RAM on a warm reset where it keeps RAM (see
+//! [`retain-ram`](#retain-ram)), so the kept statics, `#[uninit]` statics and `BootInfo` survive.
+//! RAM is scrubbed whenever it's initialized.
+//!
+//! ## `upper-memory`
+//!
+//! Places the `.upper.text`, `.upper.rodata`, `.upper.bss` and `.upper.data` sections above
+//! 0x10000, on MSP430X devices with flash or FRAM up there (e.g. the MSP430F5529 and the
+//! MSP430FR5994). `memory.x` must then define two more regions: `HIROM`, for code, constants and
+//! the initial values of `.upper.data`, and `HIRAM`, for `.upper.bss` and `.upper.data`. `HIRAM`
+//! can be a part of the upper FRAM or, on devices without any, an alias of `RAM`:
+//!
+//! ``` text
+//! /* Linker script for the MSP430F5529 */
+//! MEMORY
+//! {
+//!   RAM : ORIGIN = 0x2400, LENGTH = 0x2000
+//!   ROM : ORIGIN = 0x4400, LENGTH = 0xBB80
+//!   VECTORS : ORIGIN = 0xFF80, LENGTH = 0x80
+//!   HIROM : ORIGIN = 0x10000, LENGTH = 0x14400
+//! }
+//!
+//! REGION_ALIAS("HIRAM", RAM);
+//! ```
+//!
+//! With the `fram-mpu` feature, the FRAM above `.persistent` is read-only, so linking fails if
+//! `HIRAM` is placed there and `.upper.bss` or `.upper.data` isn't empty, unless `__MPUSAM` allows
+//! writes to segment 3.
+//!
+//! `Reset` zeroes `.upper.bss` and initializes `.upper.data` with 20-bit instructions, after the
+//! regular sections. Rust code only deals in 16-bit addresses, so the upper sections are meant for
+//! code and data built for the large memory model, e.g. C compiled with `msp430-elf-gcc -mlarge`.
+//!
+//! Interrupt handlers have to stay below 0x10000, as the vector table only holds 16-bit addresses.
+//! `#[interrupt]` puts them in `.lower.text` sections, and `msp430-elf-gcc` in `.lowtext`. Linking
+//! fails if `Reset` or the default interrupt handler ends up above 0x10000.
+//!
+//! <div class="warning">
+//!
+//! Code in the upper sections isn't safe to interrupt with a Rust interrupt handler. Rust code is
+//! built for the 16-bit MSP430, so an `#[interrupt]` handler saves and restores only the lower 16
+//! bits of the registers it uses among r4 to r10, and code that keeps a 20-bit address in one of
+//! them finds bits 19:16 cleared when the interrupt returns. Disable interrupts around calls into
+//! the upper sections, or only enable interrupts whose handlers are built for the large memory
+//! model. The trampolines of `#[interrupt] fn DefaultHandler(irq: u8)` are the one exception: with
+//! this feature they save all 20 bits of r4 to r15 before calling the handler.
+//!
+//! </div>
+//!
+//! Without this feature the `.upper` sections are placed along with the regular ones, the same way
+//! the `.lower` and `.either` sections always are.
+//!
 //! # Inspection
 //!
 //! This section covers how to inspect a binary that builds on top of `msp430-rt`.
@@ -157,6 +519,32 @@
 //! Total                0x7b
 //! ```
 //!
+//! Functions marked with `#[ramfunc]` go in the `.ramtext` section. Like `.data`, it's stored in
+//! `ROM` and copied to `RAM` by `Reset`, so it counts towards the size of both regions.
+//!
+//! `Reset` finds the RAM sections it has to zero or copy from `ROM` in the `.init_tables` section,
+//! a few words per section that the linker script generates. Only the sections of the enabled
+//! features get an entry.
+//!
+//! Statics marked with `#[persistent]` go in the `.persistent` section, which is placed in `ROM`
+//! (FRAM) right after `.rodata` and is only initialized when the device is programmed.
+//!
+//! Statics marked with `#[uninit]` go in the `.uninit` section, which `Reset` doesn't initialize.
+//! With the `boot-info` feature it also holds the 14 bytes that `Reset` keeps [`BootInfo`] in, and
+//! with the `retain-ram` feature the 4 bytes that tell it whether RAM is initialized.
+//!
+//! With the `ram2` feature, statics marked with `#[ram2]` go in the `.bss2` and `.data2`
+//! sections, in the second RAM region.
+//!
+//! With the `info-mem` feature, statics marked with `#[info_mem]` go in the `.infoA` to `.infoD`
+//! sections, one per segment of the information memory.
+//!
+//! With the `jtag-signature` feature, the JTAG and BSL signatures go in the `.jtagsignature` and
+//! `.bslsignature` sections.
+//!
+//! With the `ipe` feature, the IPE init structure and the encapsulated code and constants go in
+//! the `.ipestruct` and `.ipe` sections, and the IPE signature in the `.ipesignature` section.
+//!
 //! Without the `-A` argument `size` reports the sum of the sizes of `.text`, `.rodata` and
 //! `.vector_table` under "text".
 //!
@@ -170,9 +558,9 @@
 //!
 //! One will always find the following (unmangled) symbols in `msp430-rt` applications:
 //!
-//! - `Reset`. This function will initialize the stack pointer, call `PreInit`, initialize static
-//! variables (`.data` and `.bss`) and then call the user program entry point using the `main`
-//! symbol (See `#[entry]`).
+//! - `Reset`. This function will initialize the stack pointer, hold the watchdog if requested, call
+//! `PreInit`, initialize static variables (`.data` and `.bss`) and RAM functions (`.ramtext`) and
+//! then call the user program entry point using the `main` symbol (See `#[entry]`).
 //!
 //!   In previous versions of this crate (0.2.4 and below), the startup code was implemented in
 //! Rust, and `main` would sometimes be inlined into `Reset` (using a `ResetTrampoline` for stack
@@ -182,6 +570,10 @@
 //! - `DefaultHandler`. This is the default interrupt handler. If not overridden using `#[interrupt]
 //! fn DefaultHandler(..` this will be an infinite loop.
 //!
+//! - `__DefaultHandler<n>`. The per-vector trampolines that pass `n` to `DefaultHandlerIrq`.
+//!
+//! - `DefaultHandlerIrq`. An `#[interrupt] fn DefaultHandler(irq: u8)`, if the program has one.
+//!
 //! - `__RESET_VECTOR`. This is the reset vector, a pointer into `ResetTrampoline`. This vector is
 //! located at the end of the `.vector_table` section.
 //!
@@ -193,6 +585,9 @@
 //! versions of this crate, an empty function marked with the `#[pre_init]` would be optimized out.
 //! As of version 0.2.5, a `PreInit` function will always be included.
 //!
+//! - `Lpm5Wakeup`. The `#[lpm5_wakeup]` function, which `Reset` calls on a wake-up from LPMx.5
+//!   with the `lpm5-wakeup` feature.
+//!
 //! If you overrode any interrupt handler you'll find it as an unmangled symbol, e.g. `NMI` or
 //! `WDT`, in the output of `objdump`.
 //!
@@ -240,6 +635,12 @@
 //! This `static` variable will be placed at `ORIGIN(VECTORS)`. This address corresponds to the
 //! spot where IRQ0 (IRQ number 0) is located.
 //!
+//! `__INTERRUPTS` must fill the `VECTORS` region, all but the reset vector at its end. Otherwise
+//! linking fails, and ld reports the difference as an overflow of the region ("region `VECTORS'
+//! overflowed by N bytes"), 2 bytes per vector. The number of vectors in `__INTERRUPTS` and the
+//! number that fit in the region are available as the `_interrupts_actual` and
+//! `_interrupts_expected` linker symbols.
+//!
 //! To conform to the MSP430 ABI `__INTERRUPTS` must be an array of function pointers; some spots
 //! in this array may need to be set to 0 if they are marked as *reserved* in the data sheet /
 //! reference manual. We recommend using a `union` to set the reserved spots to `0`; `None`
@@ -317,6 +718,23 @@
 //!
 //! This weakly aliases both `Foo` and `Bar`. `DefaultHandler` is the default interrupt handler.
 //!
+//! To let an `#[interrupt] fn DefaultHandler(irq: u8)` know which interrupt fired, alias each
+//! interrupt to the trampoline of its vector instead, `__DefaultHandler<n>`, where `n` is the
+//! position of the vector in `__INTERRUPTS`:
+//!
+//! ``` text
+//! /* device.x */
+//! PROVIDE(Foo = __DefaultHandler2);
+//! PROVIDE(Bar = __DefaultHandler4);
+//! ```
+//!
+//! The trampolines fall back to `DefaultHandler` when the application doesn't take the index.
+//!
+//! The `device.x` that svd2rust generates aliases every interrupt to `DefaultHandler`. With such a
+//! device crate, an `#[interrupt] fn DefaultHandler(irq: u8)` is always called with `irq` set to
+//! `0xff`; the index is only there for the interrupts that the device crate aliases to
+//! `__DefaultHandler<n>`.
+//!
 //! Because this linker script is provided by a dependency of the final application the dependency
 //! must contain build script that puts `device.x` somewhere the linker can find. An example of such
 //! build script is shown below:
@@ -335,17 +753,68 @@
 //! }
 //! ```
 //!
+//! ## Unused interrupt vectors and the BSL password
+//!
+//! On devices with a bootstrap loader (BSL), the contents of the interrupt vector table double as
+//! the BSL password. The vectors that no handler is bound to hold the address of the default
+//! interrupt handler: `DefaultHandler`, or its per-vector trampolines (`__DefaultHandler<n>`)
+//! without the `device` feature if the program has an `#[interrupt] fn DefaultHandler(irq: u8)`.
+//! `link.x` places these right after `Reset`, ahead of any other code. The trampolines are kept or
+//! dropped as a whole, so their addresses and the one of the default handler only change with the
+//! version of `msp430-rt` and the set of its features that is enabled. This only applies to an
+//! `#[interrupt] fn DefaultHandler` that isn't a `#[ramfunc]` and doesn't have a `#[link_section]`.
+//!
+//! To fill the unused vectors with a fixed value instead, set `__VECTOR_FILL` in `memory.x`:
+//!
+//! ``` text
+//! /* Unused vectors read as erased flash */
+//! __VECTOR_FILL = 0xFFFF;
+//! ```
+//!
+//! An interrupt that fires with its vector set to `__VECTOR_FILL` jumps to that address, so the
+//! default interrupt handler must not be overridden then; linking fails if it is. A value of 0
+//! means that `__VECTOR_FILL` is not set. With the `device` feature, `__VECTOR_FILL` replaces
+//! `DefaultHandler` in the vectors that `device.x` binds to it, but not the ones bound to a
+//! trampoline.
+//!
+//! ## Startup code for MSP430X devices
+//!
+//! The startup code comes in two variants. The default one only uses instructions of the MSP430
+//! CPU, so it runs on any device. Programs built for the MSP430X CPU, i.e. with
+//! `-C target-cpu=msp430x` or `-C target-cpu=msp430xv2`, get a variant that uses `pushm` and `popm`
+//! to save and restore registers in the default interrupt handler, which makes it smaller and
+//! faster. `build.rs` picks the variant from the flags in `RUSTFLAGS` (or `build.rustflags`), and
+//! both behave the same:
+//!
+//! ``` toml
+//! # .cargo/config.toml
+//! [target.msp430-none-elf]
+//! rustflags = ["-C", "target-cpu=msp430x"]
+//! ```
+//!
 //! [attr-entry]: attr.entry.html
 //! [attr-exception]: attr.exception.html
 //! [attr-pre_init]: attr.pre_init.html
 
 #![deny(missing_docs)]
 #![feature(abi_msp430_interrupt)]
+#![feature(linkage)]
 #![no_std]
 
+use core::{ops::Range, ptr};
 use msp430::asm;
 pub use msp430_rt_macros::interrupt;
-pub use msp430_rt_macros::{entry, pre_init};
+pub use msp430_rt_macros::{entry, info_mem, persistent, pre_init, ram2, ramfunc, uninit};
+
+#[cfg(feature = "lpm5-wakeup")]
+pub use msp430_rt_macros::lpm5_wakeup;
+
+#[cfg(feature = "boot-info")]
+mod boot;
+pub mod fram;
+
+#[cfg(feature = "boot-info")]
+pub use boot::BootInfo;
 
 /// Returns a pointer to the start of the heap
 ///
@@ -353,10 +822,99 @@ pub use msp430_rt_macros::{entry, pre_init};
 #[inline]
 pub fn heap_start() -> *mut u32 {
     extern "C" {
-        static mut __sheap: u32;
+        static mut _sheap: u32;
     }
 
-    unsafe { &mut __sheap }
+    ptr::addr_of_mut!(_sheap)
+}
+
+/// Returns a pointer to the end of the heap (one past its last byte)
+///
+/// The heap ends where the `_stack_size` bytes reserved for the stack begin, or at the end of the
+/// `RAM` region if `_stack_start` is not in it (see
+/// [`memory.x`](crate#_stack_start-and-_stack_size)). With the default `_stack_size` of 0, the
+/// heap and the stack share all the free RAM.
+///
+/// The returned pointer is guaranteed to be 4-byte aligned and not below [`heap_start`].
+#[inline]
+pub fn heap_end() -> *mut u32 {
+    extern "C" {
+        static mut _eheap: u32;
+    }
+
+    ptr::addr_of_mut!(_eheap)
+}
+
+/// Returns the size of the heap in bytes
+///
+/// The size is a multiple of 4 bytes, and may be 0.
+#[inline]
+pub fn heap_size() -> usize {
+    heap_end() as usize - heap_start() as usize
+}
+
+/// Returns the bounds of the heap
+#[inline]
+pub fn heap_range() -> Range<*mut u32> {
+    heap_start()..heap_end()
+}
+
+/// Returns the largest number of bytes the stack has used since the last reset
+///
+/// `Reset` fills the stack reservation, the `_stack_size` bytes below `_stack_start`, with a fixed
+/// pattern before calling `PreInit`; this function looks for the lowest word that no longer holds
+/// it.
+///
+/// The result is a lower bound: a stack slot that was reserved but never written, or that happens
+/// to hold the pattern, doesn't count. If it's as large as the stack reservation, the stack may
+/// have overflowed.
+#[cfg(feature = "paint-stack")]
+pub fn stack_high_water_mark() -> usize {
+    extern "C" {
+        static _spaint: u16;
+        static _stack_start: u16;
+    }
+
+    let end = ptr::addr_of!(_stack_start);
+    let mut p = ptr::addr_of!(_spaint);
+    while p < end && unsafe { ptr::read_volatile(p) } == STACK_PAINT {
+        p = unsafe { p.add(1) };
+    }
+
+    end as usize - p as usize
+}
+
+// The pattern `Reset` paints the stack with
+#[cfg(feature = "paint-stack")]
+const STACK_PAINT: u16 = 0xcdcd;
+
+/// Records the current contents of the retained sections, so that `Reset` keeps them on the next
+/// warm reset
+///
+/// `Reset` only skips the RAM init if a checksum of `.bss` and `.data` (and of the `ram2` and
+/// `upper-memory` sections, if enabled) matches the one it stored during the last boot or that this
+/// function stored since. Call it after updating the statics that should survive a reset; any
+/// change made after the last call makes the next reset initialize RAM again.
+///
+/// This runs with interrupts disabled, so that an interrupt handler can't change a static while
+/// the checksum is computed. It takes time proportional to the size of the retained sections.
+#[cfg(feature = "retain-ram")]
+pub fn seal_ram() {
+    extern "C" {
+        // Defined in asm.s
+        fn __RetainChecksum() -> u16;
+        static mut __RAM_RETENTION: [u16; 2];
+    }
+
+    let sr = msp430::register::sr::read();
+    msp430::interrupt::disable();
+    unsafe {
+        let checksum = __RetainChecksum();
+        ptr::write_volatile(ptr::addr_of_mut!(__RAM_RETENTION[1]), checksum);
+        if sr.gie() {
+            msp430::interrupt::enable();
+        }
+    }
 }
 
 extern "msp430-interrupt" {
@@ -379,14 +937,14 @@ extern "msp430-interrupt" fn DefaultHandler_() -> ! {
     }
 }
 
-// Interrupts for generic application
+// Interrupts for generic application. If the default handler takes the index of the vector (see
+// `#[interrupt] fn DefaultHandler(irq: u8)`), each vector goes through a trampoline that passes it.
+// build.rs generates `INTERRUPTS`, the number of vectors in the VECTORS region besides the reset
+// vector, and `VECTORS`, an array of that many trampolines (or of DefaultHandler)
+#[cfg(not(feature = "device"))]
+include!(concat!(env!("OUT_DIR"), "/interrupts.rs"));
+
 #[cfg(not(feature = "device"))]
 #[no_mangle]
 #[link_section = ".vector_table.interrupts"]
-static __INTERRUPTS: [unsafe extern "msp430-interrupt" fn(); 15] = [{
-    extern "msp430-interrupt" {
-        fn DefaultHandler();
-    }
-
-    DefaultHandler
-}; 15];
+static __INTERRUPTS: [unsafe extern "msp430-interrupt" fn(); INTERRUPTS] = VECTORS;